@@ -0,0 +1,127 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::{config, state, unix_now, verbose};
+
+/// Worktree operation recorded in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Add,
+    Remove,
+}
+
+impl Operation {
+    fn label(self) -> &'static str {
+        match self {
+            Operation::Add => "add",
+            Operation::Remove => "remove",
+        }
+    }
+}
+
+/// Appends an operation to the audit log when logging is enabled.
+///
+/// Logging is best-effort: failures are only reported with `--verbose`.
+pub fn record(operation: Operation, branch: Option<&str>, path: &Path, exit_code: Option<i32>) {
+    let Some(log_path) = log_path() else {
+        return;
+    };
+    let repo = state::state_dir()
+        .ok()
+        .and_then(|dir| dir.parent().map(Path::to_path_buf))
+        .unwrap_or_default();
+    let line = format_entry(unix_now(), operation, &repo, branch, path, exit_code);
+    if let Err(err) = append(&log_path, &line) {
+        verbose(format!("Could not write audit log: {err:#}"));
+    }
+}
+
+/// Resolves the log file from `GWW_LOG_FILE`, or the default location when enabled in config.
+fn log_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("GWW_LOG_FILE").filter(|path| !path.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    if !config::get().audit_log {
+        return None;
+    }
+    let base = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state"))
+        })?;
+    Some(base.join("gww").join("log"))
+}
+
+/// Appends one line to the log, creating its directory if needed.
+fn append(path: &Path, line: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Formats a tab-separated entry: time, operation, repo, branch, path, git exit code.
+///
+/// Missing branches and exit codes (e.g. git killed by a signal) are written as `-`.
+fn format_entry(
+    timestamp_unix: i64,
+    operation: Operation,
+    repo: &Path,
+    branch: Option<&str>,
+    path: &Path,
+    exit_code: Option<i32>,
+) -> String {
+    format!(
+        "{timestamp_unix}\t{}\t{}\t{}\t{}\t{}\n",
+        operation.label(),
+        repo.display(),
+        branch.unwrap_or("-"),
+        path.display(),
+        exit_code.map_or_else(|| "-".to_string(), |code| code.to_string())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes one tab-separated line with placeholders for unknown fields.
+    #[test]
+    fn format_entry_uses_placeholders_for_missing_fields() {
+        let added = format_entry(
+            1700000000,
+            Operation::Add,
+            Path::new("/repo/.git"),
+            Some("feature"),
+            Path::new("/wt/repo/feature"),
+            Some(0),
+        );
+        let removed = format_entry(
+            1700000001,
+            Operation::Remove,
+            Path::new("/repo/.git"),
+            None,
+            Path::new("/wt/repo/detached"),
+            None,
+        );
+
+        assert_eq!(
+            added,
+            "1700000000\tadd\t/repo/.git\tfeature\t/wt/repo/feature\t0\n"
+        );
+        assert_eq!(
+            removed,
+            "1700000001\tremove\t/repo/.git\t-\t/wt/repo/detached\t-\n"
+        );
+    }
+}
@@ -0,0 +1,605 @@
+use anyhow::{Context, Result};
+use console::Style;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Name of the committable per-repo environment file.
+pub const REPO_ENV_FILE: &str = ".gww.env";
+
+/// Settings read from the gww config file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Color selector timestamps by age, like `checkout --age-color`.
+    pub age_color: bool,
+    /// Day thresholds for the `today`, `week`, and `month` age colors.
+    pub age_color_days: Option<Vec<u32>>,
+    /// Sparse-checkout patterns applied to new worktrees.
+    pub sparse_patterns: Vec<String>,
+    /// Ordering for branches whose commit times are equal.
+    pub tie_break: TieBreak,
+    /// Per-source selector ordering; unset sources sort by commit time.
+    pub sort: SortOrder,
+    /// Glob patterns for local and remote branches hidden from the selector.
+    pub ignore_branches: Vec<String>,
+    /// Append worktree adds and removes to `~/.local/state/gww/log`.
+    pub audit_log: bool,
+    /// Subcommand run when `gww` is invoked without one (default `checkout`).
+    pub default_command: Option<String>,
+    /// Run `git fetch --all --prune` before `checkout` lists branches.
+    pub fetch_before_checkout: bool,
+    /// Extra attempts for a fetch that failed with a network error (default 2).
+    pub fetch_retries: Option<u32>,
+    /// Wait before the first fetch retry in milliseconds, doubled for each
+    /// further one (default 1000).
+    pub fetch_retry_delay_ms: Option<u64>,
+    /// Initialize submodules recursively in new worktrees.
+    pub submodules: bool,
+    /// Create missing branches in `checkout` instead of asking whether to.
+    pub default_create: bool,
+    /// Start new branches at the default branch instead of `HEAD`.
+    pub base_branch_detect: bool,
+    /// Answer preselected when `checkout` asks to create a missing branch (default true).
+    pub create_confirm_default: Option<bool>,
+    /// Text of that question, with `{branch}` and `{base}` placeholders.
+    pub create_prompt: Option<String>,
+    /// What `checkout` does with a typed name matching no local or remote branch.
+    pub on_missing_remote: OnMissingRemote,
+    /// Prefix prepended to branches `checkout` creates, e.g. `"kev/"`.
+    pub branch_prefix: Option<String>,
+    /// Ask whether a new slashed branch gets a nested or a flat directory.
+    pub flat_dir_prompt: bool,
+    /// Branch name template for `checkout --scratch`.
+    pub scratch_name: Option<String>,
+    /// Worktree path template with `{root}`, `{owner}`, `{repo}`, and `{branch}`.
+    pub worktree_layout: Option<String>,
+    /// Where `checkout` opens the worktree (default: cd the current shell).
+    pub open_in: OpenIn,
+    /// Command run by `checkout --terminal`, with `{path}` and `{branch}` placeholders.
+    pub terminal_command: Option<String>,
+    /// Directory whose contents are copied into every new worktree.
+    pub template: Option<PathBuf>,
+    /// Whether the selector separates worktree, local, and remote groups (default false).
+    pub sections: Option<bool>,
+    /// Whether the current branch leads the worktree group (default true).
+    pub current_first: Option<bool>,
+    /// Whether the default branch is pinned to the top of the selector.
+    pub main_first: Option<bool>,
+    /// Short names that expand to branch names in `checkout`.
+    pub aliases: HashMap<String, String>,
+    /// Git config set in every new worktree, as dotted keys and values.
+    #[serde(deserialize_with = "git_config_entries")]
+    pub worktree_config: Vec<(String, String)>,
+    /// Source labels shown in the selector's `[..]` tag.
+    pub labels: Labels,
+    /// Styles for the parts of each selector entry.
+    pub theme: Theme,
+}
+
+impl Config {
+    /// Age color thresholds in days, defaulting to 1, 7, and 30.
+    pub fn age_color_days(&self) -> [u32; 3] {
+        match self.age_color_days.as_deref() {
+            Some(&[today, week, month]) => [today, week, month],
+            _ => [1, 7, 30],
+        }
+    }
+
+    /// Rejects age thresholds that are not three increasing day counts.
+    fn validate_age_color_days(&self) -> Result<()> {
+        let Some(days) = self.age_color_days.as_deref() else {
+            return Ok(());
+        };
+        let increasing = days.first().is_some_and(|&first| first > 0)
+            && days.windows(2).all(|pair| pair[0] < pair[1]);
+        if days.len() != 3 || !increasing {
+            anyhow::bail!(
+                "age_color_days must be three increasing day counts (today, week, month), e.g. [1, 7, 30]"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Selector source labels; unset ones keep the single-letter defaults.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Labels {
+    pub worktree: Option<String>,
+    pub local: Option<String>,
+    pub remote: Option<String>,
+}
+
+impl Labels {
+    /// Label for branches checked out in a worktree.
+    pub fn worktree(&self) -> &str {
+        self.worktree.as_deref().unwrap_or("T")
+    }
+
+    /// Label for local branches without a worktree.
+    pub fn local(&self) -> &str {
+        self.local.as_deref().unwrap_or("L")
+    }
+
+    /// Label for remote branches.
+    pub fn remote(&self) -> &str {
+        self.remote.as_deref().unwrap_or("R")
+    }
+}
+
+/// Selector styles as console dotted strings such as `"cyan.bold"` or `"#ff8800"`.
+///
+/// Unset elements keep the built-in look; an empty string means unstyled.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub tag: Option<String>,
+    pub worktree_tag: Option<String>,
+    pub local_tag: Option<String>,
+    pub remote_tag: Option<String>,
+    pub branch: Option<String>,
+    pub subject: Option<String>,
+    pub author: Option<String>,
+    pub timestamp: Option<String>,
+}
+
+impl Theme {
+    /// Style for the `[T*]` source tag.
+    pub fn tag(&self) -> Style {
+        theme_style(&self.tag, "cyan.bold")
+    }
+
+    /// Tag style for worktree branches, falling back to [`Theme::tag`].
+    pub fn worktree_tag(&self) -> Style {
+        self.source_tag(&self.worktree_tag)
+    }
+
+    /// Tag style for local branches, falling back to [`Theme::tag`].
+    pub fn local_tag(&self) -> Style {
+        self.source_tag(&self.local_tag)
+    }
+
+    /// Tag style for remote branches, falling back to [`Theme::tag`].
+    pub fn remote_tag(&self) -> Style {
+        self.source_tag(&self.remote_tag)
+    }
+
+    fn source_tag(&self, value: &Option<String>) -> Style {
+        match value {
+            Some(value) => Style::from_dotted_str(value),
+            None => self.tag(),
+        }
+    }
+
+    /// Style for the branch name.
+    pub fn branch(&self) -> Style {
+        theme_style(&self.branch, "")
+    }
+
+    /// Style for the commit subject.
+    pub fn subject(&self) -> Style {
+        theme_style(&self.subject, "magenta")
+    }
+
+    /// Style for the commit author.
+    pub fn author(&self) -> Style {
+        theme_style(&self.author, "yellow")
+    }
+
+    /// Style for the commit timestamp.
+    pub fn timestamp(&self) -> Style {
+        theme_style(&self.timestamp, "dim")
+    }
+
+    /// Rejects style strings containing parts console does not understand.
+    fn validate(&self) -> Result<()> {
+        let elements = [
+            ("tag", &self.tag),
+            ("worktree_tag", &self.worktree_tag),
+            ("local_tag", &self.local_tag),
+            ("remote_tag", &self.remote_tag),
+            ("branch", &self.branch),
+            ("subject", &self.subject),
+            ("author", &self.author),
+            ("timestamp", &self.timestamp),
+        ];
+        for (element, value) in elements {
+            let Some(value) = value.as_deref().filter(|value| !value.is_empty()) else {
+                continue;
+            };
+            if let Some(part) = value
+                .split('.')
+                .find(|part| Style::from_dotted_str(part) == Style::new())
+            {
+                anyhow::bail!(
+                    "Unknown style '{part}' in theme.{element}; use colors like red, on_blue, bright, 208, or #ff8800 and attributes like bold, dim, underlined"
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a configured style, falling back to the built-in default when unset.
+fn theme_style(value: &Option<String>, default: &str) -> Style {
+    Style::from_dotted_str(value.as_deref().unwrap_or(default))
+}
+
+/// Selector sort keys for each branch source.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SortOrder {
+    pub worktree: Option<SortKey>,
+    pub local: Option<SortKey>,
+    pub remote: Option<SortKey>,
+}
+
+/// How one group of selector branches is ordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortKey {
+    /// Most recent commit first.
+    Commit,
+    /// Most recently visited first.
+    Accessed,
+    /// Alphabetical by branch name.
+    Name,
+}
+
+/// What `checkout` does when a typed branch is neither local nor fetched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnMissingRemote {
+    /// Create a new local branch, asking first unless creation is the default.
+    #[default]
+    Create,
+    /// Fail with a hint to fetch.
+    Error,
+    /// Fetch all remotes and look again before creating.
+    Fetch,
+}
+
+/// Where `checkout` opens the resolved worktree.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OpenIn {
+    /// Print a cd line for the shell function.
+    #[default]
+    Cd,
+    /// Open a new tmux window in the worktree.
+    Tmux,
+    /// Run the configured `terminal_command`.
+    Terminal,
+}
+
+/// How branches with identical commit times are ordered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TieBreak {
+    /// Alphabetical by branch name.
+    #[default]
+    NameAsc,
+    /// Reverse alphabetical by branch name.
+    NameDesc,
+    /// The order git listed the branches in.
+    Insertion,
+}
+
+/// Loads the config file and makes it available through [`get`].
+pub fn init() -> Result<()> {
+    let config = match config_path() {
+        Some(path) => load(&path)?,
+        None => Config::default(),
+    };
+    let _ = CONFIG.set(config);
+    Ok(())
+}
+
+/// Returns the loaded config, or defaults when none was loaded.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// Resolves the config file location from `GWW_CONFIG` or the XDG config dir.
+fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("GWW_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("gww").join("config.toml"))
+}
+
+/// Reads a config file, treating a missing file as empty.
+fn load(path: &Path) -> Result<Config> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read {}", path.display()));
+        }
+    };
+    parse(&contents).with_context(|| format!("Invalid config in {}", path.display()))
+}
+
+/// Reads `KEY=VALUE` pairs from a repo env file, returning none when absent.
+pub fn read_env_file(path: &Path) -> Result<Vec<(String, String)>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(parse_env_file(&contents)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Variables a repo env file is allowed to set.
+///
+/// A cloned repository must not pick the config file (`GWW_CONFIG`), the git
+/// binary, the audit log, or the cd prefix, which would let it run code or
+/// write files of its choosing. `GWW_WORKTREE_LAYOUT` is left out too: layouts
+/// expand environment variables and need not stay under the worktree root.
+pub const REPO_ENV_KEYS: &[&str] = &[
+    "WORKTREE_ROOT",
+    "GWW_IGNORE",
+    "GWW_NO_COLOUR",
+    "GWW_DEFAULT_CREATE",
+];
+
+/// Returns true for variables a repo env file is allowed to set.
+pub fn is_repo_env_key(key: &str) -> bool {
+    REPO_ENV_KEYS.contains(&key)
+}
+
+/// Parses dotenv-style lines, skipping comments and malformed entries.
+fn parse_env_file(contents: &str) -> Vec<(String, String)> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+            let key = key.trim();
+            if key.is_empty() {
+                return None;
+            }
+            Some((key.to_string(), unquote(value.trim()).to_string()))
+        })
+        .collect()
+}
+
+/// Strips one pair of matching single or double quotes.
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return &value[1..value.len() - 1];
+        }
+    }
+    value
+}
+
+/// Reads the `[worktree_config]` table as `(dotted key, value)` pairs.
+///
+/// Nested tables join their keys with dots, so `[worktree_config.user]` with
+/// `email = "..."` becomes `user.email`, like a quoted `"user.email"` key.
+fn git_config_entries<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<(String, String)>, D::Error> {
+    let table = toml::Table::deserialize(deserializer)?;
+    let mut entries = Vec::new();
+    flatten_git_config("", &table, &mut entries).map_err(serde::de::Error::custom)?;
+    Ok(entries)
+}
+
+/// Appends the leaves of a config table under `prefix`.
+fn flatten_git_config(
+    prefix: &str,
+    table: &toml::Table,
+    entries: &mut Vec<(String, String)>,
+) -> std::result::Result<(), String> {
+    for (key, value) in table {
+        let name = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        let value = match value {
+            toml::Value::Table(inner) => {
+                flatten_git_config(&name, inner, entries)?;
+                continue;
+            }
+            toml::Value::String(text) => text.clone(),
+            toml::Value::Boolean(flag) => flag.to_string(),
+            toml::Value::Integer(number) => number.to_string(),
+            _ => {
+                return Err(format!(
+                    "worktree_config.{name} must be a string, boolean, or integer"
+                ));
+            }
+        };
+        entries.push((name, value));
+    }
+    Ok(())
+}
+
+/// Parses config file contents.
+fn parse(contents: &str) -> Result<Config> {
+    let config: Config = toml::from_str(contents)?;
+    config.theme.validate()?;
+    config.validate_age_color_days()?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads sparse patterns and defaults missing keys.
+    #[test]
+    fn parse_reads_sparse_patterns() {
+        let config = parse("sparse_patterns = [\"src\", \"docs\"]\n").expect("valid config");
+        let empty = parse("").expect("empty config");
+
+        assert_eq!(config.sparse_patterns, vec!["src", "docs"]);
+        assert!(empty.sparse_patterns.is_empty());
+    }
+
+    /// Reads kebab-case tie-break names.
+    #[test]
+    fn parse_reads_tie_break() {
+        let config = parse("tie_break = \"name-desc\"\n").expect("valid config");
+
+        assert_eq!(config.tie_break, TieBreak::NameDesc);
+        assert_eq!(parse("").expect("empty").tie_break, TieBreak::NameAsc);
+        assert!(parse("tie_break = \"random\"\n").is_err());
+    }
+
+    /// Parses comments, exports, and quoted values in env files.
+    #[test]
+    fn parse_env_file_handles_comments_exports_and_quotes() {
+        let contents = "# shared defaults\nWORKTREE_ROOT=../worktrees\nexport GWW_IGNORE=\"dependabot/*\"\nGWW_CD_PREFIX='>> '\nnot a pair\n=missing\n";
+
+        assert_eq!(
+            parse_env_file(contents),
+            vec![
+                ("WORKTREE_ROOT".to_string(), "../worktrees".to_string()),
+                ("GWW_IGNORE".to_string(), "dependabot/*".to_string()),
+                ("GWW_CD_PREFIX".to_string(), ">> ".to_string()),
+            ]
+        );
+    }
+
+    /// Restricts repo env files to gww settings.
+    #[test]
+    fn is_repo_env_key_allows_only_gww_settings() {
+        assert!(is_repo_env_key("WORKTREE_ROOT"));
+        assert!(is_repo_env_key("GWW_IGNORE"));
+        assert!(!is_repo_env_key("PATH"));
+        for key in [
+            "GWW_GIT_BIN",
+            "GWW_CONFIG",
+            "GWW_LOG_FILE",
+            "GWW_CD_PREFIX",
+            "GWW_WORKTREE_LAYOUT",
+        ] {
+            assert!(!is_repo_env_key(key), "{key} must not be settable");
+        }
+    }
+
+    /// Drops `GWW_CONFIG` from a repo env file so a clone cannot load its own config.
+    #[test]
+    fn repo_env_file_ignores_gww_config() {
+        let entries: Vec<_> = parse_env_file("GWW_CONFIG=./evil.toml\nGWW_IGNORE=wip/*\n")
+            .into_iter()
+            .filter(|(key, _)| is_repo_env_key(key))
+            .collect();
+
+        assert_eq!(
+            entries,
+            vec![("GWW_IGNORE".to_string(), "wip/*".to_string())]
+        );
+    }
+
+    /// Flattens nested and dotted worktree config keys and stringifies values.
+    #[test]
+    fn parse_flattens_worktree_config() {
+        let config = parse(
+            "[worktree_config]\n\"pull.rebase\" = true\ncore.hooksPath = \".githooks\"\n\n[worktree_config.user]\nemail = \"me@work.example\"\n",
+        )
+        .expect("valid");
+
+        assert_eq!(
+            config.worktree_config,
+            vec![
+                ("core.hooksPath".to_string(), ".githooks".to_string()),
+                ("pull.rebase".to_string(), "true".to_string()),
+                ("user.email".to_string(), "me@work.example".to_string()),
+            ]
+        );
+        assert!(parse("[worktree_config]\nx = [1]\n").is_err());
+    }
+
+    /// Reads the aliases table.
+    #[test]
+    fn parse_reads_aliases() {
+        let config = parse("[aliases]\nm = \"main\"\nrel = \"release/2024\"\n").expect("valid");
+
+        assert_eq!(config.aliases.get("m").map(String::as_str), Some("main"));
+        assert_eq!(
+            config.aliases.get("rel").map(String::as_str),
+            Some("release/2024")
+        );
+    }
+
+    /// Leaves ordering toggles unset unless configured.
+    #[test]
+    fn parse_reads_ordering_toggles() {
+        let config = parse("current_first = false\nmain_first = true\n").expect("valid");
+
+        assert_eq!(config.current_first, Some(false));
+        assert_eq!(config.main_first, Some(true));
+        assert_eq!(parse("").expect("empty").current_first, None);
+    }
+
+    /// Reads theme styles and rejects unknown color names.
+    #[test]
+    fn parse_validates_theme_styles() {
+        let config = parse("[theme]\ntag = \"blue.bold\"\nauthor = \"\"\n").expect("valid theme");
+
+        assert_eq!(config.theme.tag(), Style::new().blue().bold());
+        assert_eq!(config.theme.author(), Style::new());
+        assert_eq!(config.theme.subject(), Style::new().magenta());
+        assert!(parse("[theme]\nsubject = \"magneta\"\n").is_err());
+        assert!(parse("[theme]\nheader = \"red\"\n").is_err());
+    }
+
+    /// Reads source labels and per-source tag styles, falling back to the shared tag.
+    #[test]
+    fn parse_reads_source_labels_and_styles() {
+        let config =
+            parse("[labels]\nworktree = \"wt\"\n[theme]\ntag = \"blue\"\nremote_tag = \"dim\"\n")
+                .expect("valid config");
+
+        assert_eq!(config.labels.worktree(), "wt");
+        assert_eq!(config.labels.local(), "L");
+        assert_eq!(config.theme.remote_tag(), Style::new().dim());
+        assert_eq!(config.theme.local_tag(), Style::new().blue());
+        assert!(parse("[theme]\nlocal_tag = \"blu\"\n").is_err());
+    }
+
+    /// Reads age color thresholds and rejects lists that are not three increasing days.
+    #[test]
+    fn parse_validates_age_color_days() {
+        let config = parse("age_color_days = [2, 14, 60]\n").expect("valid config");
+
+        assert_eq!(config.age_color_days(), [2, 14, 60]);
+        assert_eq!(parse("").expect("empty").age_color_days(), [1, 7, 30]);
+        assert!(parse("age_color_days = [7, 1, 30]\n").is_err());
+        assert!(parse("age_color_days = [1, 7]\n").is_err());
+        assert!(parse("age_color_days = [0, 7, 30]\n").is_err());
+    }
+
+    /// Reads per-source sort keys and rejects unknown ones.
+    #[test]
+    fn parse_reads_sort_order() {
+        let config =
+            parse("[sort]\nworktree = \"accessed\"\nremote = \"name\"\n").expect("valid config");
+
+        assert_eq!(config.sort.worktree, Some(SortKey::Accessed));
+        assert_eq!(config.sort.local, None);
+        assert_eq!(config.sort.remote, Some(SortKey::Name));
+        assert!(parse("[sort]\nlocal = \"random\"\n").is_err());
+    }
+
+    /// Rejects misspelled keys instead of silently ignoring them.
+    #[test]
+    fn parse_rejects_unknown_keys() {
+        assert!(parse("sparse_pattern = [\"src\"]\n").is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::io::Write;
+
+/// A JSON value in a JSON Lines record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value<'a> {
+    Str(&'a str),
+    Int(i64),
+    Bool(bool),
+    Null,
+}
+
+impl<'a> From<Option<&'a str>> for Value<'a> {
+    fn from(value: Option<&'a str>) -> Self {
+        value.map_or(Value::Null, Value::Str)
+    }
+}
+
+/// Formats fields as a single-line JSON object, keeping their order.
+pub fn object(fields: &[(&str, Value)]) -> String {
+    let mut line = String::from("{");
+    for (index, (key, value)) in fields.iter().enumerate() {
+        if index > 0 {
+            line.push(',');
+        }
+        line.push_str(&quote(key));
+        line.push(':');
+        match value {
+            Value::Str(text) => line.push_str(&quote(text)),
+            Value::Int(number) => line.push_str(&number.to_string()),
+            Value::Bool(flag) => line.push_str(if *flag { "true" } else { "false" }),
+            Value::Null => line.push_str("null"),
+        }
+    }
+    line.push('}');
+    line
+}
+
+/// Joins already formatted values into a single-line JSON array.
+pub fn array(items: &[String]) -> String {
+    format!("[{}]", items.join(","))
+}
+
+/// Writes one record to stdout and flushes it so consumers can stream results.
+pub fn emit(line: &str) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{line}")
+        .and_then(|()| stdout.flush())
+        .context("Failed to write to stdout")
+}
+
+/// Quotes a string, escaping what JSON requires.
+fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            ch if u32::from(ch) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", u32::from(ch));
+            }
+            ch => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Keeps field order and escapes quotes, backslashes, and control characters.
+    #[test]
+    fn object_escapes_strings_and_keeps_order() {
+        let line = object(&[
+            ("branch", Value::Str("feat/\"x\"")),
+            ("path", Value::Str("C:\\wt\n\u{1}")),
+            ("staged", Value::Int(2)),
+            ("main", Value::Bool(false)),
+            ("error", Value::from(None)),
+        ]);
+
+        assert_eq!(
+            line,
+            r#"{"branch":"feat/\"x\"","path":"C:\\wt\n\u0001","staged":2,"main":false,"error":null}"#
+        );
+    }
+
+    /// Joins items with commas and renders an empty list as `[]`.
+    #[test]
+    fn array_joins_items() {
+        let items = vec![
+            object(&[("branch", Value::Str("main"))]),
+            object(&[("branch", Value::Null)]),
+        ];
+
+        assert_eq!(array(&items), r#"[{"branch":"main"},{"branch":null}]"#);
+        assert_eq!(array(&[]), "[]");
+    }
+}
@@ -0,0 +1,83 @@
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::Path;
+
+const LOCK_FILE: &str = ".gww.lock";
+
+/// Advisory lock that serializes worktree creation and removal under one root.
+///
+/// Held until dropped; the OS also releases it if gww exits abnormally, so a
+/// leftover lock file never blocks later runs.
+pub struct RootLock {
+    file: File,
+}
+
+impl RootLock {
+    /// Blocks until no other gww process holds the lock for `root`.
+    ///
+    /// Returns `None` without creating anything when `root` does not exist yet.
+    pub fn acquire(root: &Path) -> Result<Option<RootLock>> {
+        if !root.is_dir() {
+            return Ok(None);
+        }
+        let path = root.join(LOCK_FILE);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                eprintln!(
+                    "Waiting for another gww process to finish in {}",
+                    root.display()
+                );
+                file.lock()
+                    .with_context(|| format!("Failed to lock {}", path.display()))?;
+            }
+            Err(TryLockError::Error(err)) => {
+                return Err(err).with_context(|| format!("Failed to lock {}", path.display()));
+            }
+        }
+        Ok(Some(RootLock { file }))
+    }
+}
+
+impl Drop for RootLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Keeps other openers out until the lock is dropped.
+    #[test]
+    fn lock_is_exclusive_until_dropped() {
+        let root = std::env::temp_dir().join(format!("gww-lock-{}", std::process::id()));
+        fs::create_dir_all(&root).expect("create root");
+        let lock = RootLock::acquire(&root)
+            .expect("acquire lock")
+            .expect("root exists");
+        let other = File::open(root.join(LOCK_FILE)).expect("open lock file");
+
+        assert!(matches!(other.try_lock(), Err(TryLockError::WouldBlock)));
+        drop(lock);
+        assert!(other.try_lock().is_ok());
+        fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    /// Leaves a missing root alone instead of creating it just to lock.
+    #[test]
+    fn missing_root_is_not_created() {
+        let root = std::env::temp_dir().join(format!("gww-lock-missing-{}", std::process::id()));
+
+        assert!(RootLock::acquire(&root).expect("no error").is_none());
+        assert!(!root.exists());
+    }
+}
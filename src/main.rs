@@ -1,20 +1,61 @@
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use console::style;
-use dialoguer::{Confirm, FuzzySelect};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use console::{Style, style};
+use dialoguer::{Confirm, FuzzySelect, Input, Select};
 use std::collections::{HashMap, HashSet};
 use std::env;
 use std::ffi::OsStr;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use std::time::Instant;
+use std::process::{Command, Output, Stdio};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+mod audit;
+mod config;
+mod jsonl;
+mod lock;
+mod spinner;
+mod state;
+
+use config::TieBreak;
 
 const CD_PREFIX: &str = "GWW_CD:";
+const ERR_PREFIX: &str = "GWW_ERR:";
+const CREATE_BRANCH_ITEM: &str = "[+] Create new branch...";
+const MIN_GIT_VERSION: GitVersion = GitVersion(2, 17, 0);
+const SPARSE_CHECKOUT_GIT_VERSION: GitVersion = GitVersion(2, 25, 0);
+const WORKTREE_LOCK_GIT_VERSION: GitVersion = GitVersion(2, 10, 0);
+const ORPHAN_WORKTREE_GIT_VERSION: GitVersion = GitVersion(2, 42, 0);
+const WORKTREE_REPAIR_GIT_VERSION: GitVersion = GitVersion(2, 30, 0);
+const WORKTREE_CONFIG_GIT_VERSION: GitVersion = GitVersion(2, 20, 0);
+const DEFAULT_SCRATCH_NAME: &str = "scratch/{date}-{time}";
+const EXISTS_ERROR_CODE: i32 = 3;
+const AVAILABLE_SOURCES: [BranchSource; 2] = [BranchSource::Local, BranchSource::Remote];
+const MAX_NAME_SUFFIX: u32 = 1000;
+
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+static PRINT_CMD: AtomicBool = AtomicBool::new(false);
+static QUIET_GIT: AtomicBool = AtomicBool::new(false);
+static CHILD_OWNS_INTERRUPT: AtomicBool = AtomicBool::new(false);
+static REPO_DIR: OnceLock<PathBuf> = OnceLock::new();
+static LAUNCHER: OnceLock<Launcher> = OnceLock::new();
 
 #[derive(Parser)]
 #[command(name = "gww", about = "Git worktree wrapper", version)]
 struct Cli {
+    /// Accept every confirmation prompt without asking
+    #[arg(short = 'y', long = "yes", global = true)]
+    yes: bool,
+    /// Explain how gww resolves branches and paths
+    #[arg(short = 'v', long = "verbose", global = true)]
+    verbose: bool,
+    /// Run against the repository at PATH instead of the current directory
+    #[arg(long = "repo", value_name = "PATH", global = true)]
+    repo: Option<PathBuf>,
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -23,16 +64,10 @@ struct Cli {
 enum Commands {
     /// Checkout a branch in a worktree
     #[command(alias = "co")]
-    Checkout {
-        /// Branch name to checkout
-        branch: Option<String>,
-        /// Create branch if it does not exist
-        #[arg(short = 'b')]
-        create: bool,
-    },
+    Checkout(Box<CheckoutArgs>),
     /// List worktrees
     #[command(alias = "ls")]
-    List,
+    List(ListArgs),
     /// Remove a worktree
     #[command(alias = "rm")]
     Remove {
@@ -41,19 +76,341 @@ enum Commands {
         /// Force removal without prompting
         #[arg(short = 'f', long = "force")]
         force: bool,
+        /// Offer the main worktree in the selector too
+        #[arg(long = "include-main")]
+        include_main: bool,
+        /// Delete the branch after removing its worktree (`-D` with --force)
+        #[arg(long = "delete-branch", overrides_with = "keep_branch")]
+        delete_branch: bool,
+        /// Keep the branch after removing its worktree (the default)
+        #[arg(long = "keep-branch")]
+        keep_branch: bool,
+    },
+    /// Print every branch the selector would offer, with its source and last commit
+    Branches {
+        /// Only show branches from this source (repeatable)
+        #[arg(long = "source", value_enum)]
+        source: Vec<BranchSource>,
+        /// Output format; `names` prints bare branch names for scripts
+        #[arg(long = "format", value_enum, default_value_t = BranchesFormat::Selector)]
+        format: BranchesFormat,
+    },
+    /// Exit 0 if a branch has a worktree, 1 if only the branch exists, 2 otherwise, 3 on error
+    Exists {
+        /// Branch name to check
+        branch: String,
+    },
+    /// Recreate the worktree removed last, at the same path
+    Undo,
+    /// Fix git's worktree records after worktree directories were moved by hand
+    Repair {
+        /// New locations of moved worktrees, which git cannot find on its own
+        paths: Vec<PathBuf>,
     },
+    /// Print the directory new worktrees of this repository are created under
+    Root,
     /// Output shell function for auto-cd
     Autocd,
+    /// Check environment and configuration health
+    Doctor,
+    /// Print the forge compare/PR URL for the current branch
+    #[command(alias = "pr")]
+    Compare {
+        /// Open the URL with the system opener instead of printing it
+        #[arg(long = "open")]
+        open: bool,
+    },
     #[command(hide = true)]
-    Timechooser,
+    Timechooser {
+        /// Output format for the timing report
+        #[arg(long = "format", value_enum, default_value_t = TimingFormat::Human)]
+        format: TimingFormat,
+    },
     #[command(external_subcommand)]
     External(Vec<String>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Args, Debug, Default)]
+struct CheckoutArgs {
+    /// Branch name to checkout
+    branch: Option<String>,
+    /// Create branch if it does not exist
+    #[arg(short = 'b', overrides_with = "no_create")]
+    create: bool,
+    /// Ask before creating a missing branch, overriding `GWW_DEFAULT_CREATE` and the config file
+    #[arg(long = "no-create")]
+    no_create: bool,
+    /// Create the branch from the most recent branch matching GLOB (e.g. 'release/*')
+    #[arg(long = "from-latest", value_name = "GLOB")]
+    from_latest: Option<String>,
+    /// Create the branch from REF; `-` reads the ref from stdin
+    #[arg(
+        long = "from",
+        value_name = "REF",
+        requires = "branch",
+        conflicts_with_all = ["from_latest", "stdin", "scratch"]
+    )]
+    from: Option<String>,
+    /// Print each `git worktree add` command to stderr before running it
+    #[arg(long = "print-cmd")]
+    print_cmd: bool,
+    /// Start new branches at the default branch (origin/HEAD) instead of HEAD
+    #[arg(long = "base-branch-detect", overrides_with = "no_base_branch_detect")]
+    base_branch_detect: bool,
+    /// Start new branches at HEAD, overriding the config file
+    #[arg(long = "no-base-branch-detect")]
+    no_base_branch_detect: bool,
+    /// Stack the new branch on local branch PARENT, recording the relationship
+    #[arg(
+        long = "on",
+        value_name = "PARENT",
+        requires = "branch",
+        conflicts_with_all = ["from", "from_latest", "stdin", "scratch", "detach"]
+    )]
+    on: Option<String>,
+    /// Prefix for newly created branch names, overriding `branch_prefix` (e.g. 'kev/')
+    #[arg(long = "prefix", value_name = "PREFIX")]
+    prefix: Option<String>,
+    /// Add a detached worktree at the given commit instead of checking out a branch
+    #[arg(
+        long = "detach",
+        requires = "branch",
+        conflicts_with_all = ["create", "scratch", "stdin", "from_latest", "from", "prefer_remote"]
+    )]
+    detach: bool,
+    /// Only offer branches that already have a worktree, skipping the branch scan
+    #[arg(
+        long = "worktrees-only",
+        conflicts_with_all = ["branch", "stdin", "scratch", "all_remotes", "prefer_remote"]
+    )]
+    worktrees_only: bool,
+    /// Create worktrees for branch names read from stdin, one per line, without prompting
+    #[arg(
+        long = "stdin",
+        conflicts_with_all = ["branch", "scratch", "path", "dir_name", "from_latest"]
+    )]
+    stdin: bool,
+    /// Create a throwaway branch off HEAD named from the `scratch_name` template
+    #[arg(long = "scratch", conflicts_with_all = ["branch", "create", "from_latest"])]
+    scratch: bool,
+    /// Sparse-checkout pattern for new worktrees (repeatable)
+    #[arg(long = "sparse", value_name = "PATTERN")]
+    sparse: Vec<String>,
+    /// Lock the new worktree against pruning, optionally with `--lock=REASON`
+    #[arg(
+        long = "lock",
+        value_name = "REASON",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ""
+    )]
+    lock: Option<String>,
+    /// Let the configured template replace files from the checkout
+    #[arg(long = "overwrite-template")]
+    overwrite_template: bool,
+    /// Create the worktree at this directory instead of the computed path
+    #[arg(long = "path", value_name = "DIR")]
+    path: Option<PathBuf>,
+    /// Fail instead of creating missing parent directories for the worktree
+    #[arg(long = "no-create-dirs")]
+    no_create_dirs: bool,
+    /// Adopt an existing non-empty directory at the worktree path, keeping its files
+    #[arg(long = "reattach")]
+    reattach: bool,
+    /// Register the new worktree without checking out files (`git worktree add --no-checkout`)
+    #[arg(long = "no-checkout", conflicts_with = "reattach")]
+    no_checkout: bool,
+    /// Name the worktree directory NAME instead of after the branch
+    #[arg(long = "dir-name", value_name = "NAME", conflicts_with = "path")]
+    dir_name: Option<String>,
+    /// Change into the branch's worktree when it already has one (the default)
+    #[arg(long = "reuse-existing", overrides_with = "error_if_exists")]
+    reuse_existing: bool,
+    /// Fail instead of reusing a worktree the branch already has
+    #[arg(long = "error-if-exists")]
+    error_if_exists: bool,
+    /// Use a matching remote branch even when a local branch of that name exists
+    #[arg(long = "prefer-remote")]
+    prefer_remote: bool,
+    /// Take the branch from this remote when several remotes have it
+    #[arg(long = "remote", value_name = "NAME", requires = "branch")]
+    remote: Option<String>,
+    /// What to do when a typed branch is neither local nor a fetched remote branch
+    #[arg(long = "on-missing-remote", value_enum, value_name = "POLICY")]
+    on_missing_remote: Option<MissingRemotePolicy>,
+    /// Fast-forward a local branch that is behind its upstream without asking
+    #[arg(long = "ff")]
+    ff: bool,
+    /// Run `git fetch --all --prune` first so remote branches are current
+    #[arg(long = "fetch-all")]
+    fetch_all: bool,
+    /// List every remote's branches, even when a local branch has the same name
+    #[arg(long = "all-remotes")]
+    all_remotes: bool,
+    /// Show branches matching the ignore patterns in the selector
+    #[arg(long = "no-ignore")]
+    no_ignore: bool,
+    /// Only offer branches with commits after this date (e.g. "2 weeks ago")
+    #[arg(long = "since", value_name = "DATE")]
+    since: Option<String>,
+    /// Only offer branches with commits before this date
+    #[arg(long = "until", value_name = "DATE")]
+    until: Option<String>,
+    /// Order selector branches by commit, accessed, or name; prefix worktree=, local=, or
+    /// remote= to order one group (repeatable)
+    #[arg(long = "sort", value_name = "[SOURCE=]KEY", value_parser = parse_sort_spec)]
+    sort: Vec<SortSpec>,
+    /// Show ahead/behind counts against each branch's upstream in the selector
+    #[arg(long = "track-info")]
+    track_info: bool,
+    /// Color selector timestamps by commit age and dim stale branches
+    #[arg(long = "age-color")]
+    age_color: bool,
+    /// Show only branch names in the selector, skipping commit metadata (sorted by name)
+    #[arg(long = "no-summary", conflicts_with_all = ["since", "until", "track_info", "age_color"])]
+    no_summary: bool,
+    /// Separate worktree, local, and remote groups in the selector
+    #[arg(long = "sections", overrides_with = "no_sections")]
+    sections: bool,
+    /// Show the selector as one flat list (the default)
+    #[arg(long = "no-sections")]
+    no_sections: bool,
+    /// List the current branch first among worktrees (the default)
+    #[arg(long = "current-first", overrides_with = "no_current_first")]
+    current_first: bool,
+    /// Order worktree branches strictly by recency
+    #[arg(long = "no-current-first")]
+    no_current_first: bool,
+    /// Pin the repository's default branch to the top of the selector
+    #[arg(long = "main-first", overrides_with = "no_main_first")]
+    main_first: bool,
+    /// Do not pin the default branch, overriding the config file
+    #[arg(long = "no-main-first")]
+    no_main_first: bool,
+    /// Initialize submodules recursively in a new worktree
+    #[arg(long = "submodules", overrides_with = "no_submodules")]
+    submodules: bool,
+    /// Skip submodule initialization, overriding the environment and config file
+    #[arg(long = "no-submodules")]
+    no_submodules: bool,
+    /// Open the worktree in a new tmux window instead of changing directory
+    #[arg(long = "tmux", conflicts_with_all = ["terminal", "cd"])]
+    tmux: bool,
+    /// Open the worktree with the configured terminal_command instead of changing directory
+    #[arg(long = "terminal", conflicts_with = "cd")]
+    terminal: bool,
+    /// Change directory in the current shell, overriding open_in in the config file
+    #[arg(long = "cd")]
+    cd: bool,
+}
+
+#[derive(Args, Debug, Default)]
+struct ListArgs {
+    /// Print only the number of worktrees
+    #[arg(long = "count")]
+    count: bool,
+    /// Leave the main worktree out of the count
+    #[arg(long = "exclude-main", requires = "count")]
+    exclude_main: bool,
+    /// List local and remote branches that have no worktree yet
+    #[arg(long = "available", conflicts_with = "count")]
+    available: bool,
+    /// Print each worktree using a template such as '{branch}\t{path}'
+    #[arg(
+        long = "format",
+        value_name = "TEMPLATE",
+        conflicts_with_all = ["count", "available"]
+    )]
+    format: Option<String>,
+    /// Stream one JSON object per worktree with its change counts (JSON Lines)
+    #[arg(long = "porcelain", conflicts_with_all = ["count", "available", "format"])]
+    porcelain: bool,
+    /// Print a JSON array of worktree health (dirty, untracked, ahead, behind)
+    #[arg(
+        long = "json",
+        conflicts_with_all = ["count", "available", "format", "porcelain"]
+    )]
+    json: bool,
+    /// Order worktrees instead of using git's order
+    #[arg(long = "sort", value_enum, conflicts_with_all = ["count", "available"])]
+    sort: Option<ListSort>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ListSort {
+    /// Most recent commit first
+    Recent,
+    /// Alphabetical by branch name
+    Alpha,
+    /// Alphabetical by path
+    Path,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum MissingRemotePolicy {
+    /// Create a new local branch (the default)
+    Create,
+    /// Fail with a hint to fetch
+    Error,
+    /// Fetch all remotes, then look for the branch again
+    Fetch,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum BranchSort {
+    /// Most recent commit first
+    #[default]
+    Commit,
+    /// Most recently visited first, then by commit for unvisited branches
+    Accessed,
+    /// Alphabetical by branch name
+    Name,
+}
+
+/// A `--sort` value, applying to one branch source or to all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SortSpec {
+    source: Option<BranchSource>,
+    key: BranchSort,
+}
+
+/// Sort key for each group of selector branches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct SourceSorts {
+    worktree: BranchSort,
+    local: BranchSort,
+    remote: BranchSort,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BranchesFormat {
+    /// Entries as the selector shows them
+    Selector,
+    /// One branch name per line, without tags, details, or color
+    Names,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum TimingFormat {
+    Human,
+    Tsv,
+}
+
+#[derive(Debug, Clone, Default)]
 struct WorktreeInfo {
     path: PathBuf,
     branch: Option<String>,
+    head: Option<String>,
+    /// The main worktree, which git always lists first.
+    is_main: bool,
+    /// HEAD is not on a branch, e.g. `checkout --detach` or a rebase in progress.
+    detached: bool,
+    /// The bare repository entry, which has no checkout.
+    bare: bool,
+    /// Lock reason; empty when locked without one.
+    locked: Option<String>,
+    /// Why `git worktree prune` would remove the entry, usually a missing directory.
+    prunable: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -62,6 +419,8 @@ struct BranchInfo {
     source: BranchSource,
     summary: BranchSummary,
     is_current: bool,
+    /// Last commit time, or `None` when metadata is missing.
+    timestamp_unix: Option<i64>,
 }
 
 #[derive(Debug, Clone)]
@@ -69,6 +428,13 @@ struct BranchSummary {
     timestamp_label: String,
     author: String,
     subject: String,
+    track: Option<UpstreamTrack>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UpstreamTrack {
+    Counts { ahead: u32, behind: u32 },
+    Gone,
 }
 
 #[derive(Debug, Clone)]
@@ -77,36 +443,204 @@ struct BranchMeta {
     summary: BranchSummary,
 }
 
+#[derive(Debug, Clone, Default)]
+struct CandidateOptions {
+    ignore: Vec<String>,
+    since_unix: Option<i64>,
+    until_unix: Option<i64>,
+    track_info: bool,
+    age_color: bool,
+    no_summary: bool,
+    sections: bool,
+    current_first: bool,
+    pinned_main: Option<String>,
+    all_remotes: bool,
+    sorts: SourceSorts,
+    access_times: Option<HashMap<String, i64>>,
+    /// Sources offered in the selector; empty offers all of them.
+    sources: Vec<BranchSource>,
+}
+
+/// A row in the branch selector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SelectorEntry {
+    /// Divider above a source group.
+    Header(BranchSource),
+    /// Index into the candidate list.
+    Branch(usize),
+    /// The synthetic create-branch entry.
+    Create,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BranchSelection {
+    Existing(String),
+    Create { name: String, base: Option<String> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct GitVersion(u32, u32, u32);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RemoteRepo {
+    host: String,
+    owner: String,
+    name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BranchState {
+    Worktree(PathBuf),
+    BranchOnly,
+    Missing,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone)]
+struct CheckResult {
+    status: CheckStatus,
+    name: &'static str,
+    detail: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
 enum BranchSource {
+    /// Local branches without a worktree
     Local,
+    /// Remote-tracking branches
     Remote,
+    /// Branches checked out in a worktree
     Worktree,
 }
 
+/// Program that opens the checked out worktree in place of a shell cd.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Launcher {
+    Tmux,
+    /// Whitespace-separated command template from `terminal_command`.
+    Terminal(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExistingPath {
+    Missing,
+    EmptyDir,
+    Occupied,
+    Checkout,
+    File,
+}
+
+/// `git worktree add` refused because the branch is checked out in another worktree.
+///
+/// This covers worktrees gww cannot see as holding the branch, such as one in
+/// the middle of a rebase, where `git worktree list` shows a detached HEAD.
+#[derive(Debug)]
+struct CheckedOutElsewhere {
+    path: PathBuf,
+    message: String,
+}
+
+impl std::fmt::Display for CheckedOutElsewhere {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "git worktree add failed: {}", self.message)
+    }
+}
+
+impl std::error::Error for CheckedOutElsewhere {}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct StatusCounts {
+    staged: u32,
+    unstaged: u32,
+    untracked: u32,
+}
+
+/// A piece of a `{placeholder}` template, see [`template_parts`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TemplatePart<'a> {
+    Literal(&'a str),
+    Placeholder(&'a str),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FormatPart {
+    Literal(String),
+    Field(ListField),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AgeBucket {
+    Today,
+    Week,
+    Month,
+    Older,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListField {
+    Path,
+    Branch,
+    Subject,
+    Author,
+    Time,
+    Ahead,
+    Behind,
+}
+
 /// Entry point for the gww CLI.
 fn main() -> Result<()> {
-    configure_colors();
     let cli = Cli::parse();
+    configure_repo(cli.repo.as_deref())?;
+    load_repo_env_file()?;
+    install_interrupt_handler()?;
+    configure_colors();
+    configure_prompts(cli.yes);
+    VERBOSE.store(cli.verbose, Ordering::Relaxed);
+    config::init()?;
     let command = match cli.command {
         Some(command) => command,
         None => {
-            eprintln!(
-                "No command provided; defaulting to `checkout`. Use `gww --help` for options."
-            );
-            return checkout(None, false);
+            let name = config::get()
+                .default_command
+                .as_deref()
+                .unwrap_or("checkout");
+            let command = default_command(name)?;
+            eprintln!("No command provided; defaulting to `{name}`. Use `gww --help` for options.");
+            command
         }
     };
 
     match command {
-        Commands::Checkout { branch, create } => checkout(branch, create),
-        Commands::List => list_worktrees(),
-        Commands::Remove { branch, force } => remove_worktree(branch, force),
+        Commands::Checkout(args) => checkout(*args),
+        Commands::List(args) => list_worktrees(args),
+        Commands::Remove {
+            branch,
+            force,
+            include_main,
+            delete_branch,
+            keep_branch: _,
+        } => remove_worktree(branch, force, include_main, delete_branch),
+        Commands::Branches { source, format } => list_branches(&source, format),
+        Commands::Exists { branch } => exists(branch),
         Commands::Autocd => autocd(),
-        Commands::Timechooser => timechooser(),
+        Commands::Undo => undo_remove(),
+        Commands::Repair { paths } => repair_worktrees(&paths),
+        Commands::Root => print_root(),
+        Commands::Doctor => doctor(),
+        Commands::Compare { open } => compare_url(open),
+        Commands::Timechooser { format } => timechooser(format),
         Commands::External(args) => {
             if let Some(branch) = branch_from_external_args(&args) {
-                checkout(Some(branch), false)
+                checkout(CheckoutArgs {
+                    branch: Some(branch),
+                    ..CheckoutArgs::default()
+                })
             } else {
                 anyhow::bail!("Unknown command: {}", args.join(" "))
             }
@@ -114,6 +648,56 @@ fn main() -> Result<()> {
     }
 }
 
+/// Parses the configured default subcommand as if it was typed without arguments.
+fn default_command(name: &str) -> Result<Commands> {
+    let cli = Cli::command();
+    let commands: Vec<_> = cli
+        .get_subcommands()
+        .filter(|command| !command.is_hide_set())
+        .collect();
+    if !commands.iter().any(|command| {
+        command.get_name() == name || command.get_all_aliases().any(|alias| alias == name)
+    }) {
+        let names: Vec<_> = commands.iter().map(|command| command.get_name()).collect();
+        anyhow::bail!(
+            "Unknown default_command '{name}' in config; use one of {}",
+            names.join(", ")
+        );
+    }
+    match Cli::try_parse_from(["gww", name]) {
+        Ok(Cli {
+            command: Some(command),
+            ..
+        }) => Ok(command),
+        _ => anyhow::bail!(
+            "default_command '{name}' in config needs arguments; pick a command that runs on its own"
+        ),
+    }
+}
+
+/// Applies `.gww.env` from the repo root without overriding the real environment.
+fn load_repo_env_file() -> Result<()> {
+    let Ok(root) = git_output(["rev-parse", "--show-toplevel"]) else {
+        return Ok(());
+    };
+    let path = Path::new(root.trim()).join(config::REPO_ENV_FILE);
+    for (key, value) in config::read_env_file(&path)? {
+        if !config::is_repo_env_key(&key) {
+            warn(format!(
+                "ignoring {key} in {}; only {} are supported",
+                path.display(),
+                config::REPO_ENV_KEYS.join(", ")
+            ));
+            continue;
+        }
+        if env::var_os(&key).is_none() {
+            // SAFETY: called at startup before any other threads are spawned.
+            unsafe { env::set_var(&key, value) };
+        }
+    }
+    Ok(())
+}
+
 /// Returns a branch name when provided as a single unrecognized argument.
 fn branch_from_external_args(args: &[String]) -> Option<String> {
     if args.len() == 1 && !args[0].starts_with('-') {
@@ -124,753 +708,6421 @@ fn branch_from_external_args(args: &[String]) -> Option<String> {
 }
 
 /// Checkout or create a worktree for the selected branch.
-fn checkout(branch: Option<String>, create: bool) -> Result<()> {
+fn checkout(mut args: CheckoutArgs) -> Result<()> {
+    args.branch = trimmed_branch_arg(args.branch)?;
     ensure_git_repo()?;
+    PRINT_CMD.store(args.print_cmd, Ordering::Relaxed);
+    if let Some(launcher) = resolve_launcher(&args)? {
+        let _ = LAUNCHER.set(launcher);
+    }
+    if args.branch.as_deref() == Some("-") {
+        return checkout_previous();
+    }
+    if let Some(dir_name) = args.dir_name.as_deref() {
+        validate_dir_name(dir_name)?;
+    }
+    if args.scratch {
+        let name = scratch_branch_name()?;
+        verbose(format!("Scratch branch name: {name}"));
+        return create_branch_worktree(&args, &name, None);
+    }
+    if args.fetch_all || config::get().fetch_before_checkout {
+        fetch_all_remotes();
+    }
+    if args.stdin {
+        return checkout_batch(&args);
+    }
+    if args.detach {
+        return checkout_detached(&args);
+    }
+    if args.worktrees_only {
+        return checkout_from_worktrees(&args);
+    }
     let worktrees = list_worktrees_info()?;
     let local_branches = list_local_branches()?;
     let remote_branches = list_remote_branches()?;
 
-    let selected_branch = match branch {
-        Some(branch) => branch,
-        None => select_branch(&worktrees, &local_branches, &remote_branches)?,
+    let selected_branch = match args.branch.clone() {
+        Some(branch) => {
+            let branch = expand_alias(branch);
+            let known = local_branches.contains(&branch)
+                || match_remote_branch(&branch, &remote_branches).is_some();
+            if args.from_latest.is_some() {
+                return create_branch_worktree(&args, &new_branch_name(&args, &branch), None);
+            }
+            if let Some(parent) = args.on.as_deref() {
+                let branch = new_branch_name(&args, &branch);
+                return create_stacked_worktree(&args, &branch, parent);
+            }
+            if let Some(from) = args.from.as_deref() {
+                let base = resolve_from_ref(from)?;
+                let branch = new_branch_name(&args, &branch);
+                return create_branch_worktree(&args, &branch, Some(&base));
+            }
+            if known {
+                branch
+            } else if args.create {
+                new_branch_name(&args, &branch)
+            } else if let Some(worktree_branch) = resolve_worktree_shorthand(&worktrees, &branch)? {
+                worktree_branch
+            } else {
+                if let Some(remote_ref) = apply_missing_remote_policy(&args, &branch)? {
+                    return checkout_remote_branch(&args, &worktrees, &remote_ref);
+                }
+                new_branch_name(&args, &branch)
+            }
+        }
+        None => match select_branch(
+            &worktrees,
+            &local_branches,
+            &remote_branches,
+            &candidate_options(&args)?,
+        )? {
+            BranchSelection::Existing(name) => name,
+            BranchSelection::Create { name, base } => {
+                let name = new_branch_name(&args, &name);
+                return create_branch_worktree(&args, &name, base.as_deref());
+            }
+        },
     };
 
-    if let Some(existing) = worktree_for_branch(&worktrees, &selected_branch) {
-        emit_cd(&existing.path);
-        return Ok(());
+    if let Some(existing) = pick_worktree_for_branch(&worktrees, &selected_branch)? {
+        if existing.path.exists() {
+            return reuse_worktree(&args, existing);
+        }
+        discard_missing_worktree(existing)?;
     }
 
-    if local_branches.iter().any(|b| b == &selected_branch) {
-        ensure_branch_or_prompt(&selected_branch, create, None)?;
-        let path = worktree_path_for_branch(&selected_branch)?;
-        git_worktree_add(&path, Some(&selected_branch), None)?;
-        emit_cd(&path);
-        return Ok(());
+    let has_local = local_branches.iter().any(|b| b == &selected_branch);
+    let remote_match = if args.prefer_remote || !has_local {
+        pick_remote_branch(&args, &selected_branch, &remote_branches)?
+    } else {
+        None
+    };
+    if args.prefer_remote
+        && let Some(remote_ref) = remote_match.as_deref()
+    {
+        return checkout_remote_branch(&args, &worktrees, remote_ref);
     }
 
-    if let Some(remote_ref) = match_remote_branch(&selected_branch, &remote_branches) {
-        let local_name = strip_remote_prefix(&remote_ref);
-        if let Some(existing) = worktree_for_branch(&worktrees, &local_name) {
-            emit_cd(&existing.path);
-            return Ok(());
-        }
-        ensure_branch_or_prompt(&local_name, create, Some(&remote_ref))?;
-        let path = worktree_path_for_branch(&local_name)?;
-        git_worktree_add(&path, Some(&local_name), Some(&remote_ref))?;
-        emit_cd(&path);
-        return Ok(());
+    if has_local {
+        ensure_branch_or_prompt(&selected_branch, should_create_branches(&args), None, None)?;
+        sync_with_upstream(&selected_branch, args.ff)?;
+        return add_worktree(&args, &selected_branch, None);
+    }
+
+    if let Some(remote_ref) = remote_match.as_deref() {
+        return checkout_remote_branch(&args, &worktrees, remote_ref);
     }
 
-    ensure_branch_or_prompt(&selected_branch, create, None)?;
-    let path = worktree_path_for_branch(&selected_branch)?;
-    git_worktree_add(&path, Some(&selected_branch), None)?;
-    emit_cd(&path);
+    let recorded = recorded_base(&selected_branch);
+    let detected = match recorded {
+        Some(_) => None,
+        None => detected_base(&args),
+    };
+    let base = recorded.or(detected.clone());
+    ensure_branch_or_prompt(
+        &selected_branch,
+        should_create_branches(&args),
+        None,
+        base.as_deref(),
+    )?;
+    add_worktree(&args, &selected_branch, base.as_deref())?;
+    if let Some(detected) = detected.as_deref() {
+        untrack_detected_base(&selected_branch, detected);
+    }
     Ok(())
 }
 
-/// Prints the raw git worktree list.
-fn list_worktrees() -> Result<()> {
-    let output = git_output(["worktree", "list"])?;
-    print!("{}", output);
-    Ok(())
+/// Formats one `--stdin` progress line, indenting multi-line details such as git's stderr.
+fn batch_status_line(status: &str, branch: &str, detail: &str) -> String {
+    let detail = detail.trim().replace('\n', "\n\t");
+    format!("{status:<8} {branch}\t{detail}")
 }
 
-/// Measures branch candidate building time.
-fn timechooser() -> Result<()> {
-    ensure_git_repo()?;
-    let start = Instant::now();
+/// Runs the selector over worktree branches only, without listing local or remote branches.
+fn checkout_from_worktrees(args: &CheckoutArgs) -> Result<()> {
     let worktrees = list_worktrees_info()?;
-    let local_branches = list_local_branches()?;
-    let remote_branches = list_remote_branches()?;
-    let candidates = build_branch_candidates(&worktrees, &local_branches, &remote_branches)?;
-    let elapsed = start.elapsed();
+    let options = candidate_options(args)?;
+    let branch = match select_branch(&worktrees, &[], &[], &options)? {
+        BranchSelection::Existing(name) => name,
+        BranchSelection::Create { name, base } => {
+            let name = new_branch_name(args, &name);
+            return create_branch_worktree(args, &name, base.as_deref());
+        }
+    };
+    match pick_worktree_for_branch(&worktrees, &branch)? {
+        Some(existing) if existing.path.exists() => reuse_worktree(args, existing),
+        Some(existing) => {
+            discard_missing_worktree(existing)?;
+            add_worktree(args, &branch, None)
+        }
+        None => add_worktree(args, &branch, None),
+    }
+}
 
-    println!(
-        "Built {} branch entries in {:.2?}",
-        candidates.len(),
-        elapsed
-    );
+/// Creates a worktree for every branch named on stdin and prints a summary instead of a cd.
+///
+/// Never prompts: missing branches are created from HEAD, remote branches get
+/// a tracking local branch, and any question takes its default answer, so
+/// safety checks refuse and fail that branch unless `--yes` was given. Git's
+/// own output is held back (unless `--verbose`) so each branch gets exactly
+/// one status line.
+fn checkout_batch(args: &CheckoutArgs) -> Result<()> {
+    NON_INTERACTIVE.store(true, Ordering::Relaxed);
+    QUIET_GIT.store(!VERBOSE.load(Ordering::Relaxed), Ordering::Relaxed);
+    let input = std::io::read_to_string(std::io::stdin()).context("Failed to read stdin")?;
+    let branches = parse_branch_list(&input);
+    let remote_branches = list_remote_branches()?;
+    let (mut created, mut existing, mut failed) = (0, 0, 0);
+    for branch in &branches {
+        let worktrees = list_worktrees_info()?;
+        if let Some(worktree) = worktree_for_branch(&worktrees, branch) {
+            if args.error_if_exists {
+                let detail = format!("already has a worktree at {}", worktree.path.display());
+                println!("{}", batch_status_line("failed", branch, &detail));
+                failed += 1;
+            } else {
+                let detail = worktree.path.display().to_string();
+                println!("{}", batch_status_line("exists", branch, &detail));
+                existing += 1;
+            }
+            continue;
+        }
+        let result = match match_remote_branch(branch, &remote_branches) {
+            Some(remote_ref) if !branch_exists(branch) => create_worktree(
+                args,
+                Some(&strip_remote_prefix(&remote_ref)),
+                Some(&remote_ref),
+            ),
+            _ => create_worktree(args, Some(branch), None),
+        };
+        match result {
+            Ok(path) => {
+                let detail = path.display().to_string();
+                println!("{}", batch_status_line("created", branch, &detail));
+                created += 1;
+            }
+            Err(err) => {
+                println!(
+                    "{}",
+                    batch_status_line("failed", branch, &format!("{err:#}"))
+                );
+                failed += 1;
+            }
+        }
+    }
+    println!("{created} created, {existing} skipped, {failed} failed");
+    if failed > 0 {
+        anyhow::bail!(
+            "{failed} of {} worktrees could not be created",
+            branches.len()
+        );
+    }
     Ok(())
 }
 
-/// Removes the selected worktree from disk.
-fn remove_worktree(branch: Option<String>, force: bool) -> Result<()> {
-    ensure_git_repo()?;
-    let worktrees = list_worktrees_info()?;
-    let selected_branch = match branch {
-        Some(branch) => branch,
-        None => select_worktree_branch(&worktrees)?,
+/// Resolves the `--from` start point, reading it from stdin for `-`, and checks it exists.
+fn resolve_from_ref(from: &str) -> Result<String> {
+    let base = if from == "-" {
+        let input = std::io::read_to_string(std::io::stdin()).context("Failed to read stdin")?;
+        parse_stdin_ref(&input)?
+    } else {
+        from.to_string()
     };
-    let worktree = worktree_for_branch(&worktrees, &selected_branch)
-        .with_context(|| format!("No worktree found for branch '{selected_branch}'"))?;
-    git_worktree_remove(&worktree.path, force)?;
-    Ok(())
+    if !ref_resolves(&base) {
+        anyhow::bail!("Base ref '{base}' does not resolve to a commit");
+    }
+    Ok(base)
 }
 
-/// Prints shell functions that auto-cd into worktrees.
-fn autocd() -> Result<()> {
-    let script = format!(
-        "gww() {{\n    local output\n    output=$(command gww \"$@\")\n    local exit_code=$?\n    echo \"$output\"\n    if [ $exit_code -eq 0 ]; then\n        local cd_path\n        cd_path=$(echo \"$output\" | grep \"^{prefix}\" | cut -d: -f2-)\n        [ -n \"$cd_path\" ] && cd \"$cd_path\"\n    fi\n    return $exit_code\n}}\n\n_gww_cd() {{\n    local output\n    output=$(command gww checkout \"$@\")\n    local exit_code=$?\n    if [ $exit_code -ne 0 ]; then\n        echo \"$output\"\n        return $exit_code\n    fi\n    local cd_path\n    cd_path=$(echo \"$output\" | grep \"^{prefix}\" | cut -d: -f2-)\n    [ -n \"$cd_path\" ] && cd \"$cd_path\"\n}}\n",
-        prefix = CD_PREFIX
-    );
+/// Takes the single ref piped to `--from -`, ignoring surrounding blank lines.
+fn parse_stdin_ref(input: &str) -> Result<String> {
+    let mut lines = input.lines().map(str::trim).filter(|line| !line.is_empty());
+    match (lines.next(), lines.next()) {
+        (Some(line), None) => Ok(line.to_string()),
+        (None, _) => anyhow::bail!("No base ref on stdin for --from -"),
+        (Some(_), Some(_)) => anyhow::bail!("Expected a single base ref on stdin for --from -"),
+    }
+}
 
-    print!("{}", script);
-    Ok(())
+/// Reads branch names one per line, skipping blanks, `#` comments, and repeats.
+fn parse_branch_list(input: &str) -> Vec<String> {
+    let mut branches: Vec<String> = Vec::new();
+    for name in input.lines().map(str::trim) {
+        if name.is_empty() || name.starts_with('#') || branches.iter().any(|b| b == name) {
+            continue;
+        }
+        branches.push(name.to_string());
+    }
+    branches
 }
 
-/// Ensures the current directory is inside a git repository.
-fn ensure_git_repo() -> Result<()> {
-    git_output(["rev-parse", "--show-toplevel"]).context("Not a git repository")?;
+/// Emits the worktree visited before the current one, like `cd -`.
+fn checkout_previous() -> Result<()> {
+    let visits = state::load_visits()?;
+    let Some(previous) = previous_visit(&visits) else {
+        anyhow::bail!(
+            "No previous worktree in history; gww records one each time it changes directory"
+        );
+    };
+    if !previous.path.exists() {
+        anyhow::bail!(
+            "Previous worktree {} no longer exists",
+            previous.path.display()
+        );
+    }
+    let branch = Some(previous.branch.as_str()).filter(|branch| !branch.is_empty());
+    emit_cd(&previous.path, branch);
     Ok(())
 }
 
-/// Runs a git command and returns stdout on success.
-fn git_output<I, S>(args: I) -> Result<String>
-where
-    I: IntoIterator<Item = S>,
-    S: AsRef<OsStr>,
-{
-    let output = Command::new("git").args(args).output()?;
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        anyhow::bail!(stderr.trim().to_string());
+/// Returns the latest visit to a different path than the most recent one.
+///
+/// Repeated visits to the same worktree are skipped so `co -` always moves.
+fn previous_visit(visits: &[state::Visit]) -> Option<&state::Visit> {
+    let (latest, earlier) = visits.split_last()?;
+    earlier.iter().rev().find(|visit| visit.path != latest.path)
+}
+
+/// Exits with a status describing whether a branch and its worktree exist.
+///
+/// Failures (not a repository, git missing) exit with [`EXISTS_ERROR_CODE`] so
+/// scripts can tell them apart from the answers.
+fn exists(branch: String) -> Result<()> {
+    let branch = expand_alias(branch);
+    let state = ensure_git_repo().and_then(|()| {
+        Ok(branch_state(
+            &list_worktrees_info()?,
+            &list_local_branches()?,
+            &list_remote_branches()?,
+            &branch,
+        ))
+    });
+    let state = match state {
+        Ok(state) => state,
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            std::process::exit(EXISTS_ERROR_CODE);
+        }
+    };
+    let code = match &state {
+        BranchState::Worktree(path) => {
+            verbose(format!(
+                "Branch '{branch}' has a worktree at {}",
+                path.display()
+            ));
+            0
+        }
+        BranchState::BranchOnly => {
+            verbose(format!("Branch '{branch}' exists but has no worktree"));
+            1
+        }
+        BranchState::Missing => {
+            verbose(format!("Branch '{branch}' does not exist"));
+            2
+        }
+    };
+    std::process::exit(code)
+}
+
+/// Classifies a branch by whether it has a worktree on disk or exists at all.
+///
+/// Remote branches count as existing, and their worktree is looked up by the
+/// local name `checkout` would use.
+fn branch_state(
+    worktrees: &[WorktreeInfo],
+    locals: &[String],
+    remotes: &[String],
+    branch: &str,
+) -> BranchState {
+    let remote = match_remote_branch(branch, remotes);
+    let local_name = match &remote {
+        Some(remote) if !locals.iter().any(|local| local == branch) => strip_remote_prefix(remote),
+        _ => branch.to_string(),
+    };
+    if let Some(worktree) = worktree_for_branch(worktrees, &local_name)
+        && worktree.path.exists()
+    {
+        return BranchState::Worktree(worktree.path.clone());
+    }
+    if locals.iter().any(|local| local == &local_name) || remote.is_some() {
+        BranchState::BranchOnly
+    } else {
+        BranchState::Missing
     }
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
 }
 
-/// Loads detailed worktree entries from git.
-fn list_worktrees_info() -> Result<Vec<WorktreeInfo>> {
-    let output = git_output(["worktree", "list", "--porcelain"])?;
-    let mut worktrees = Vec::new();
-    let mut current_path: Option<PathBuf> = None;
-    let mut current_branch: Option<String> = None;
+/// Checks out a remote branch through its local counterpart.
+///
+/// Normally this only runs when no local branch matched. With
+/// `--prefer-remote`, or when a remote was picked under `--all-remotes`, an
+/// existing local branch without a worktree is first fast-forwarded to the
+/// remote and set to track it.
+fn checkout_remote_branch(
+    args: &CheckoutArgs,
+    worktrees: &[WorktreeInfo],
+    remote_ref: &str,
+) -> Result<()> {
+    let local_name = strip_remote_prefix(remote_ref);
+    if let Some(existing) = pick_worktree_for_branch(worktrees, &local_name)? {
+        if existing.path.exists() {
+            return reuse_worktree(args, existing);
+        }
+        discard_missing_worktree(existing)?;
+    }
+    if branch_exists(&local_name) {
+        if args.prefer_remote || args.all_remotes {
+            retrack_local_branch(&local_name, remote_ref)?;
+        }
+        return add_worktree(args, &local_name, None);
+    }
+    ensure_branch_or_prompt(
+        &local_name,
+        should_create_branches(args),
+        Some(remote_ref),
+        None,
+    )?;
+    add_worktree(args, &local_name, Some(remote_ref))
+}
 
-    for line in output.lines() {
-        if line.starts_with("worktree ") {
-            if let Some(path) = current_path.take() {
-                worktrees.push(WorktreeInfo {
-                    path,
-                    branch: current_branch.take(),
-                });
-            }
-            current_path = Some(PathBuf::from(line.trim_start_matches("worktree ")));
-        } else if line.starts_with("branch ") {
-            let branch = line.trim_start_matches("branch ").trim();
-            current_branch = branch.strip_prefix("refs/heads/").map(|b| b.to_string());
-        }
-    }
-    if let Some(path) = current_path {
-        worktrees.push(WorktreeInfo {
-            path,
-            branch: current_branch,
-        });
+/// Fast-forwards a local branch to a remote branch and tracks it.
+///
+/// A branch with commits the remote lacks is never moved, like in
+/// [`sync_with_upstream`]; it is left as is with a warning.
+fn retrack_local_branch(local: &str, remote_ref: &str) -> Result<()> {
+    if !is_ancestor(local, remote_ref) {
+        warn(format!(
+            "local '{local}' has commits not on '{remote_ref}'; leaving it as is"
+        ));
+        return Ok(());
     }
+    verbose(format!(
+        "Fast-forwarding '{local}' to '{remote_ref}' and tracking it"
+    ));
+    git_output(["branch", "--force", "--track", local, remote_ref])
+        .with_context(|| format!("Failed to fast-forward '{local}' to '{remote_ref}'"))?;
+    Ok(())
+}
 
-    Ok(worktrees)
+/// How a local branch should be brought in line with its upstream before checkout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UpstreamAction {
+    None,
+    FastForward { behind: u32 },
+    Diverged { ahead: u32, behind: u32 },
 }
 
-/// Orders unique branch names by most recent commit time.
-fn sort_by_recent<I>(names: I, meta: &HashMap<String, BranchMeta>) -> Vec<String>
-where
-    I: IntoIterator,
-    I::Item: AsRef<str>,
-{
-    let mut unique: Vec<String> = Vec::new();
-    let mut seen: HashSet<String> = HashSet::new();
-    for name in names {
-        let name = name.as_ref().to_string();
-        if seen.insert(name.clone()) {
-            unique.push(name);
+/// Picks the action for a branch's upstream tracking state.
+fn upstream_action(track: Option<UpstreamTrack>) -> UpstreamAction {
+    match track {
+        Some(UpstreamTrack::Counts { ahead: 0, behind }) if behind > 0 => {
+            UpstreamAction::FastForward { behind }
         }
+        Some(UpstreamTrack::Counts { ahead, behind }) if ahead > 0 && behind > 0 => {
+            UpstreamAction::Diverged { ahead, behind }
+        }
+        _ => UpstreamAction::None,
     }
-
-    unique.sort_by(|a, b| {
-        let a_ts = meta.get(a).map(|info| info.timestamp_unix).unwrap_or(0);
-        let b_ts = meta.get(b).map(|info| info.timestamp_unix).unwrap_or(0);
-        b_ts.cmp(&a_ts).then_with(|| a.cmp(b))
-    });
-    unique
 }
 
-/// Collects commit metadata for local and remote branches.
-fn batch_branch_metadata() -> Result<HashMap<String, BranchMeta>> {
+/// Offers to fast-forward a local branch that is behind its upstream.
+///
+/// With `ff` the branch is moved without asking. Diverged branches are only
+/// warned about and never reset.
+fn sync_with_upstream(branch: &str, ff: bool) -> Result<()> {
     let output = git_output([
         "for-each-ref",
-        "refs/heads",
-        "refs/remotes",
-        "--format=%(refname:short)\t%(committerdate:unix)\t%(committerdate:iso8601-strict)\t%(authorname)\t%(subject)",
+        "--format=%(upstream:short)\t%(upstream:track)",
+        &format!("refs/heads/{branch}"),
     ])?;
-    let mut map = HashMap::new();
-    for line in output.lines() {
-        if line.trim().is_empty() {
-            continue;
+    let Some((upstream, track)) = output.trim_end().split_once('\t') else {
+        return Ok(());
+    };
+    if upstream.is_empty() {
+        return Ok(());
+    }
+    match upstream_action(parse_upstream_track(upstream, track)) {
+        UpstreamAction::None => Ok(()),
+        UpstreamAction::Diverged { ahead, behind } => {
+            warn(format!(
+                "'{branch}' has diverged from '{upstream}' (↑{ahead} ↓{behind}); leaving it as is"
+            ));
+            Ok(())
         }
-        let mut parts = line.split('\t');
-        let refname = parts.next().unwrap_or("").trim().to_string();
-        if refname.is_empty() {
-            continue;
+        UpstreamAction::FastForward { behind } => {
+            let interactive =
+                ASSUME_YES.load(Ordering::Relaxed) || console::Term::stderr().is_term();
+            let prompt =
+                format!("'{branch}' is {behind} commit(s) behind '{upstream}'. Fast-forward it?");
+            let proceed = ff || (interactive && confirm(prompt, true)?);
+            if !proceed {
+                if !interactive {
+                    warn(format!(
+                        "'{branch}' is {behind} commit(s) behind '{upstream}'; pass --ff to fast-forward it"
+                    ));
+                }
+                return Ok(());
+            }
+            verbose(format!("Fast-forwarding '{branch}' to '{upstream}'"));
+            git_output(["branch", "--force", branch, upstream])
+                .with_context(|| format!("Failed to fast-forward '{branch}' to '{upstream}'"))?;
+            Ok(())
         }
-        let timestamp_unix = parts
-            .next()
-            .and_then(|value| value.trim().parse::<i64>().ok())
-            .unwrap_or(0);
-        let timestamp_label = parts.next().unwrap_or("").trim().to_string();
-        let author = parts.next().unwrap_or("").trim().to_string();
-        let subject = parts.next().unwrap_or("").trim().to_string();
-        map.insert(
-            refname,
-            BranchMeta {
-                timestamp_unix,
-                summary: BranchSummary {
-                    timestamp_label,
-                    author,
-                    subject,
-                },
-            },
-        );
     }
-    Ok(map)
 }
 
-/// Builds a fallback branch summary when metadata is missing.
-fn placeholder_summary() -> BranchSummary {
-    BranchSummary {
-        timestamp_label: "unknown time".to_string(),
-        author: "unknown author".to_string(),
-        subject: "unknown subject".to_string(),
-    }
+/// Returns true when `ancestor` is reachable from `descendant`.
+fn is_ancestor(ancestor: &str, descendant: &str) -> bool {
+    git_command()
+        .args(["merge-base", "--is-ancestor", ancestor, descendant])
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
 }
 
-/// Formats a branch entry for display in the selector.
-fn format_branch_item(info: &BranchInfo) -> String {
-    let label = match info.source {
-        BranchSource::Worktree => "T",
-        BranchSource::Local => "L",
-        BranchSource::Remote => "R",
-    };
-    let marker = if info.is_current { "*" } else { " " };
-    let tag = format!("[{label}{marker}]");
-
-    let subject = format!("\"{}\"", info.summary.subject);
-    let author = format!("[{}]", info.summary.author);
-    let timestamp = format!("({})", info.summary.timestamp_label);
-
-    if is_color_enabled() {
-        let tag = style(tag).cyan().bold();
-        let subject = style(subject).magenta();
-        let author = style(author).yellow();
-        let timestamp = style(timestamp).dim();
-        format!("{} {} {} {} {}", tag, info.name, subject, author, timestamp)
-    } else {
-        format!(
-            "{tag:<4} {} {} {} {}",
-            info.name, subject, author, timestamp
-        )
+/// Refreshes and prunes every remote's branches, warning instead of failing when offline.
+fn fetch_all_remotes() {
+    verbose("Running git fetch --all --prune".to_string());
+    if let Err(err) = run_network_git(&["fetch", "--all", "--prune"]) {
+        warn(format!("{err:#}; using existing remote branches"));
     }
 }
 
-/// Returns true when ANSI color output is enabled.
-fn is_color_enabled() -> bool {
-    env::var("GWW_NO_COLOUR").is_err()
+/// Runs a git command that talks to remotes, retrying network failures.
+///
+/// Only errors that look transient (timeouts, DNS, dropped connections) are
+/// retried, waiting `fetch_retry_delay_ms` and doubling it each time;
+/// authentication and missing-repository errors fail at once. Local commands
+/// must not go through here.
+fn run_network_git(args: &[&str]) -> Result<()> {
+    let config = config::get();
+    let attempts = config.fetch_retries.unwrap_or(2).saturating_add(1);
+    let mut delay = Duration::from_millis(config.fetch_retry_delay_ms.unwrap_or(1000));
+    let command = format!("git {}", args.join(" "));
+    let mut attempt = 1;
+    loop {
+        let output = git_command()
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+            .map_err(git_spawn_error)?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .unwrap_or("no error output")
+            .to_string();
+        if attempt >= attempts || !is_transient_git_error(&stderr) {
+            anyhow::bail!("{command} failed: {message}");
+        }
+        verbose(format!(
+            "{command} failed (attempt {attempt} of {attempts}): {message}; retrying in {}ms",
+            delay.as_millis()
+        ));
+        std::thread::sleep(delay);
+        delay = delay.saturating_mul(2);
+        attempt += 1;
+    }
 }
 
-/// Applies the configured color settings to console output.
-fn configure_colors() {
-    if is_color_enabled() {
-        console::set_colors_enabled(true);
-    } else {
-        console::set_colors_enabled(false);
-    }
+/// Returns true when git's stderr shows a network error worth retrying.
+///
+/// Authentication, permission, and missing-repository errors win over any
+/// network wording, since retrying them cannot help.
+fn is_transient_git_error(stderr: &str) -> bool {
+    const DEFINITIVE: [&str; 6] = [
+        "authentication failed",
+        "permission denied",
+        "repository not found",
+        "could not read username",
+        "does not appear to be a git repository",
+        "host key verification failed",
+    ];
+    const TRANSIENT: [&str; 12] = [
+        "could not resolve host",
+        "temporary failure in name resolution",
+        "name or service not known",
+        "connection timed out",
+        "operation timed out",
+        "connection reset",
+        "connection refused",
+        "the remote end hung up unexpectedly",
+        "early eof",
+        "rpc failed",
+        "unexpected disconnect",
+        "network is unreachable",
+    ];
+    let stderr = stderr.to_ascii_lowercase();
+    !DEFINITIVE.iter().any(|needle| stderr.contains(needle))
+        && TRANSIENT.iter().any(|needle| stderr.contains(needle))
 }
 
-/// Lists local branch names.
-fn list_local_branches() -> Result<Vec<String>> {
-    let output = git_output(["for-each-ref", "refs/heads", "--format=%(refname:short)"])?;
-    Ok(output
-        .lines()
-        .map(|line| line.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect())
+/// Applies `--on-missing-remote` (or `on_missing_remote`) to a typed branch
+/// that matches no local or fetched remote branch.
+///
+/// Returns the remote branch when fetching turned one up; `None` falls
+/// through to creating a local branch.
+fn apply_missing_remote_policy(args: &CheckoutArgs, branch: &str) -> Result<Option<String>> {
+    let policy = args
+        .on_missing_remote
+        .unwrap_or(match config::get().on_missing_remote {
+            config::OnMissingRemote::Create => MissingRemotePolicy::Create,
+            config::OnMissingRemote::Error => MissingRemotePolicy::Error,
+            config::OnMissingRemote::Fetch => MissingRemotePolicy::Fetch,
+        });
+    match policy {
+        MissingRemotePolicy::Create => Ok(None),
+        MissingRemotePolicy::Error => anyhow::bail!(
+            "Branch '{branch}' is not a local or fetched remote branch; run `git fetch` if it is on a remote, or pass -b to create it"
+        ),
+        MissingRemotePolicy::Fetch => {
+            fetch_all_remotes();
+            let remote_ref = match_remote_branch(branch, &list_remote_branches()?);
+            match &remote_ref {
+                Some(remote_ref) => verbose(format!("Found {remote_ref} after fetching")),
+                None => verbose(format!("No remote has '{branch}' after fetching")),
+            }
+            Ok(remote_ref)
+        }
+    }
 }
 
-/// Lists remote branch names, excluding HEAD entries.
-fn list_remote_branches() -> Result<Vec<String>> {
-    let output = git_output(["for-each-ref", "refs/remotes", "--format=%(refname:short)"])?;
-    let branches = output
-        .lines()
-        .map(|line| line.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .filter(|s| s.contains('/'))
-        .filter(|s| !s.ends_with("/HEAD"))
+/// Expands a partial name to a worktree branch: exact, then unique prefix, then unique substring.
+///
+/// Returns `None` when nothing matches and fails when a step matches several
+/// branches.
+fn resolve_worktree_shorthand(worktrees: &[WorktreeInfo], name: &str) -> Result<Option<String>> {
+    let branches: Vec<&str> = worktrees
+        .iter()
+        .filter_map(|wt| wt.branch.as_deref())
         .collect();
-    Ok(branches)
+    if branches.contains(&name) {
+        return Ok(Some(name.to_string()));
+    }
+    let by_prefix: Vec<&str> = branches
+        .iter()
+        .copied()
+        .filter(|branch| branch.starts_with(name))
+        .collect();
+    let by_substring: Vec<&str> = branches
+        .iter()
+        .copied()
+        .filter(|branch| branch.contains(name))
+        .collect();
+    for (kind, found) in [("prefix", by_prefix), ("substring", by_substring)] {
+        match found.as_slice() {
+            [] => continue,
+            [branch] => {
+                verbose(format!(
+                    "'{name}' resolves to worktree branch '{branch}' by {kind}"
+                ));
+                return Ok(Some(branch.to_string()));
+            }
+            _ => anyhow::bail!(
+                "'{name}' matches several worktree branches by {kind}: {}",
+                found.join(", ")
+            ),
+        }
+    }
+    Ok(None)
 }
 
-/// Returns the currently checked-out branch name, if any.
-fn current_branch() -> Result<Option<String>> {
-    let output = git_output(["rev-parse", "--abbrev-ref", "HEAD"])?;
-    let name = output.lines().next().unwrap_or("").trim();
-    if name.is_empty() || name == "HEAD" {
-        Ok(None)
-    } else {
-        Ok(Some(name.to_string()))
+/// Replaces a configured alias with the branch it stands for.
+fn expand_alias(branch: String) -> String {
+    match config::get().aliases.get(&branch) {
+        Some(target) => {
+            verbose(format!("Alias '{branch}' expands to '{target}'"));
+            target.clone()
+        }
+        None => branch,
     }
 }
 
-/// Prompts for a branch name across worktrees, local, and remote.
-fn select_branch(
-    worktrees: &[WorktreeInfo],
-    locals: &[String],
-    remotes: &[String],
-) -> Result<String> {
-    let candidates = build_branch_candidates(worktrees, locals, remotes)?;
-
-    if candidates.is_empty() {
-        anyhow::bail!("No branches found");
+/// Applies the `--prefix` or configured `branch_prefix` to a branch about to be created.
+fn new_branch_name(args: &CheckoutArgs, name: &str) -> String {
+    let prefix = args
+        .prefix
+        .as_deref()
+        .or(config::get().branch_prefix.as_deref());
+    let branch = with_branch_prefix(name, prefix);
+    if branch != name {
+        verbose(format!("Prefixed new branch '{name}' as '{branch}'"));
     }
+    branch
+}
 
-    let items: Vec<String> = candidates.iter().map(format_branch_item).collect();
-
-    let selection = FuzzySelect::new()
-        .with_prompt("Select branch")
-        .items(&items)
-        .default(0)
-        .interact_opt()?;
+/// Prepends `prefix` unless the name already starts with it.
+fn with_branch_prefix(name: &str, prefix: Option<&str>) -> String {
+    match prefix.filter(|prefix| !prefix.is_empty()) {
+        Some(prefix) if !name.starts_with(prefix) => format!("{prefix}{name}"),
+        _ => name.to_string(),
+    }
+}
 
-    let Some(selection) = selection else {
-        anyhow::bail!("Selection cancelled");
+/// Creates a new branch from an optional base and checks it out in a worktree.
+///
+/// Without an explicit base, `--from-latest` supplies one.
+fn create_branch_worktree(args: &CheckoutArgs, branch: &str, base: Option<&str>) -> Result<()> {
+    if branch_exists(branch) {
+        anyhow::bail!("Branch '{branch}' already exists");
+    }
+    let latest = match (base, args.from_latest.as_deref()) {
+        (None, Some(pattern)) => Some(latest_matching_branch(pattern)?),
+        _ => None,
     };
+    let detected = match (base, &latest) {
+        (None, None) if !args.scratch => detected_base(args),
+        _ => None,
+    };
+    add_worktree(
+        args,
+        branch,
+        base.or(latest.as_deref()).or(detected.as_deref()),
+    )?;
+    if let Some(detected) = detected.as_deref() {
+        untrack_detected_base(branch, detected);
+    }
+    Ok(())
+}
 
-    Ok(candidates[selection].name.clone())
+/// Returns the default branch as the start point for a new branch, when enabled.
+///
+/// The remote copy (`origin/<default>`) is preferred so the branch starts at
+/// the latest fetched tip; the local branch is used without a remote.
+fn detected_base(args: &CheckoutArgs) -> Option<String> {
+    let enabled = resolve_toggle(
+        args.base_branch_detect,
+        args.no_base_branch_detect,
+        Some(config::get().base_branch_detect),
+        false,
+    );
+    if !enabled {
+        return None;
+    }
+    let name = default_branch();
+    let remote = format!("origin/{name}");
+    let base = if ref_resolves(&remote) {
+        remote
+    } else if branch_exists(&name) {
+        name
+    } else {
+        warn(format!(
+            "default branch '{name}' not found; starting the new branch at HEAD"
+        ));
+        return None;
+    };
+    verbose(format!(
+        "Starting the new branch at the default branch {base}"
+    ));
+    Some(base)
 }
 
-/// Builds branch candidates with metadata for selection.
-fn build_branch_candidates(
-    worktrees: &[WorktreeInfo],
-    locals: &[String],
-    remotes: &[String],
-) -> Result<Vec<BranchInfo>> {
-    let mut candidates: Vec<BranchInfo> = Vec::new();
-    let worktree_set: HashSet<String> = worktrees
-        .iter()
-        .filter_map(|wt| wt.branch.clone())
-        .collect();
-    let meta = batch_branch_metadata()?;
+/// Drops the upstream git sets when a branch starts at a remote-tracking branch.
+///
+/// A feature branch started at `origin/main` should not push to or pull from it.
+fn untrack_detected_base(branch: &str, base: &str) {
+    if !remote_branch_exists(base) {
+        return;
+    }
+    let _ = git_command()
+        .args(["branch", "--unset-upstream", branch])
+        .stderr(Stdio::null())
+        .status();
+}
 
-    let current_branch = current_branch()?;
-    let mut worktree_names = sort_by_recent(&worktree_set, &meta);
-    if let Some(current) = current_branch.as_ref()
-        && let Some(pos) = worktree_names.iter().position(|name| name == current)
-    {
-        let current_name = worktree_names.remove(pos);
-        worktree_names.insert(0, current_name);
+/// Creates `branch` at the tip of local branch `parent` and records the parent.
+///
+/// The parent's commit at creation is stored too, so the branch can later be
+/// rebased with `git rebase --onto <parent> <old tip> <branch>` once the
+/// parent moves.
+fn create_stacked_worktree(args: &CheckoutArgs, branch: &str, parent: &str) -> Result<()> {
+    if !branch_exists(parent) {
+        anyhow::bail!("'{parent}' is not a local branch; --on needs the parent branch to stack on");
     }
-    let local_names = sort_by_recent(locals, &meta);
-    let remote_names = sort_by_recent(remotes, &meta);
+    let parent_tip = git_output(["rev-parse", "--verify", &format!("refs/heads/{parent}")])?
+        .trim()
+        .to_string();
+    create_branch_worktree(args, branch, Some(parent))?;
+    verbose(format!(
+        "Stacked '{branch}' on '{parent}' at {}",
+        short_sha(&parent_tip)
+    ));
+    let entry = state::BranchParent {
+        branch: branch.to_string(),
+        parent: parent.to_string(),
+        parent_tip,
+        created_unix: unix_now(),
+    };
+    if let Err(err) = state::save_branch_parent(&entry) {
+        warn(format!(
+            "failed to record the parent of '{branch}': {err:#}"
+        ));
+    }
+    Ok(())
+}
 
-    for name in worktree_names {
-        let summary = meta
-            .get(&name)
-            .map(|info| info.summary.clone())
-            .unwrap_or_else(placeholder_summary);
+/// Builds a fresh branch name for `--scratch` from the configured template.
+///
+/// `{date}` and `{time}` use the local time of git's committer identity, and
+/// `{subject}` is a slug of HEAD's subject. A numeric suffix keeps the name
+/// unique when it is already taken.
+fn scratch_branch_name() -> Result<String> {
+    let template = config::get()
+        .scratch_name
+        .as_deref()
+        .unwrap_or(DEFAULT_SCRATCH_NAME);
+    let ident = git_output(["var", "GIT_COMMITTER_IDENT"])?;
+    let local_unix = parse_ident_local_time(&ident)
+        .with_context(|| format!("Unrecognized committer identity: {}", ident.trim()))?;
+    let subject = if template.contains("{subject}") {
+        git_output(["log", "-1", "--format=%s"]).unwrap_or_default()
+    } else {
+        String::new()
+    };
+    let name = render_scratch_name(template, local_unix, &subject)?;
+    let valid = git_command()
+        .args(["check-ref-format", "--branch", &name])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+    if !valid {
+        anyhow::bail!("scratch_name template produced an invalid branch name: {name}");
+    }
+    if !branch_exists(&name) {
+        return Ok(name);
+    }
+    (2..=MAX_NAME_SUFFIX)
+        .map(|n| format!("{name}-{n}"))
+        .find(|candidate| !branch_exists(candidate))
+        .with_context(|| format!("No free scratch branch name after {name}-{MAX_NAME_SUFFIX}"))
+}
 
-        candidates.push(BranchInfo {
-            is_current: current_branch.as_deref() == Some(&name),
-            summary,
+/// Fills `{date}`, `{time}`, and `{subject}` in a scratch name template.
+fn render_scratch_name(template: &str, local_unix: i64, subject: &str) -> Result<String> {
+    let (year, month, day, hour, minute) = civil_from_unix(local_unix);
+    fill_placeholders(template, |name| match name {
+        "date" => Ok(format!("{year:04}-{month:02}-{day:02}")),
+        "time" => Ok(format!("{hour:02}{minute:02}")),
+        "subject" => {
+            let slug = slugify(subject);
+            Ok(if slug.is_empty() {
+                "untitled".to_string()
+            } else {
+                slug
+            })
+        }
+        _ => Err(unknown_placeholder(
             name,
-            source: BranchSource::Worktree,
-        });
-    }
+            "scratch_name",
+            &["date", "time", "subject"],
+        )),
+    })
+}
 
-    for name in local_names {
-        if !worktree_set.contains(&name) {
-            let summary = meta
-                .get(&name)
-                .map(|info| info.summary.clone())
-                .unwrap_or_else(placeholder_summary);
+/// Reads `<unix> <+hhmm>` from the end of a git identity and returns local seconds.
+fn parse_ident_local_time(ident: &str) -> Option<i64> {
+    let mut fields = ident.split_whitespace().rev();
+    let offset = fields.next()?;
+    let unix = fields.next()?.parse::<i64>().ok()?;
+    let sign = match offset.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let hours = offset.get(1..3)?.parse::<i64>().ok()?;
+    let minutes = offset.get(3..5)?.parse::<i64>().ok()?;
+    Some(unix + sign * (hours * 3600 + minutes * 60))
+}
 
-            candidates.push(BranchInfo {
-                is_current: current_branch.as_deref() == Some(&name),
-                summary,
-                name,
-                source: BranchSource::Local,
-            });
-        }
-    }
+/// Converts seconds since the epoch to (year, month, day, hour, minute).
+fn civil_from_unix(secs: i64) -> (i64, u32, u32, u32, u32) {
+    let days = secs.div_euclid(86_400);
+    let seconds_of_day = secs.rem_euclid(86_400);
+    // Days-to-civil conversion from Howard Hinnant's date algorithms.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (
+        year,
+        month,
+        day,
+        (seconds_of_day / 3600) as u32,
+        (seconds_of_day % 3600 / 60) as u32,
+    )
+}
 
-    for name in remote_names {
-        let local_name = strip_remote_prefix(&name);
-        let has_local = locals.iter().any(|local| local == &local_name);
-        if !worktree_set.contains(&local_name) && !has_local {
-            let summary = meta
-                .get(&name)
-                .map(|info| info.summary.clone())
-                .unwrap_or_else(placeholder_summary);
-            candidates.push(BranchInfo {
-                is_current: current_branch.as_deref() == Some(&local_name),
-                summary,
-                name,
-                source: BranchSource::Remote,
-            });
+/// Lowercases `text` and joins its ASCII alphanumeric runs with `-`, capped at 40 characters.
+fn slugify(text: &str) -> String {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect();
+    let mut slug = String::new();
+    for word in words {
+        if !slug.is_empty() && slug.len() + 1 + word.len() > 40 {
+            break;
         }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word);
     }
+    slug.truncate(40);
+    slug
+}
 
-    Ok(candidates)
+/// Finds the most recently committed branch matching a glob.
+fn latest_matching_branch(pattern: &str) -> Result<String> {
+    let meta = batch_branch_metadata(false)?;
+    let remotes = list_remote_branches()?;
+    let latest = pick_latest_branch(&meta, &remotes, pattern)
+        .with_context(|| format!("No branch matches '{pattern}'"))?;
+    verbose(format!("Latest branch matching '{pattern}' is '{latest}'"));
+    Ok(latest)
 }
 
-/// Prompts for a branch among existing worktrees.
-fn select_worktree_branch(worktrees: &[WorktreeInfo]) -> Result<String> {
-    let mut branches: Vec<String> = worktrees
-        .iter()
-        .filter_map(|wt| wt.branch.clone())
-        .collect();
-    branches.sort();
-    branches.dedup();
+/// Picks the newest branch whose name matches `pattern`, ties going to the first name.
+///
+/// Remote branches also match on their name without the remote prefix, so
+/// `release/*` finds `origin/release/2.1`.
+fn pick_latest_branch(
+    meta: &HashMap<String, BranchMeta>,
+    remotes: &[String],
+    pattern: &str,
+) -> Option<String> {
+    meta.iter()
+        .filter(|(name, _)| {
+            glob_match(pattern, name)
+                || (remotes.contains(name) && glob_match(pattern, &strip_remote_prefix(name)))
+        })
+        .min_by(|(a_name, a), (b_name, b)| {
+            b.timestamp_unix
+                .cmp(&a.timestamp_unix)
+                .then_with(|| a_name.cmp(b_name))
+        })
+        .map(|(name, _)| name.clone())
+}
 
-    if branches.is_empty() {
-        anyhow::bail!("No worktrees found");
+/// Emits the path of an existing worktree, rejecting a conflicting `--path`.
+fn reuse_worktree(args: &CheckoutArgs, existing: &WorktreeInfo) -> Result<()> {
+    if args.error_if_exists {
+        anyhow::bail!(
+            "Branch '{}' already has a worktree at {}; drop --error-if-exists to reuse it",
+            existing.branch.as_deref().unwrap_or("HEAD"),
+            existing.path.display()
+        );
+    }
+    if let Some(path) = args.path.as_deref()
+        && !same_path(path, &existing.path)
+    {
+        anyhow::bail!(
+            "Branch '{}' already has a worktree at {}; --path points to {}",
+            existing.branch.as_deref().unwrap_or("HEAD"),
+            existing.path.display(),
+            path.display()
+        );
     }
+    emit_cd(&existing.path, existing.branch.as_deref());
+    Ok(())
+}
 
-    let selection = FuzzySelect::new()
-        .with_prompt("Select worktree")
-        .items(&branches)
-        .default(0)
-        .interact_opt()?;
+/// Clears the entry of a worktree whose directory was deleted so it can be recreated.
+fn discard_missing_worktree(worktree: &WorktreeInfo) -> Result<()> {
+    eprintln!(
+        "Worktree directory {} is missing; recreating it",
+        worktree.path.display()
+    );
+    remove_missing_worktree(&worktree.path)
+}
 
-    let Some(selection) = selection else {
-        anyhow::bail!("Selection cancelled");
-    };
+/// Returns true when both paths refer to the same location.
+fn same_path(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
 
-    Ok(branches[selection].clone())
+/// Returns true when two paths differ at most in letter case.
+///
+/// Branches such as `Feature` and `feature` share one directory on the
+/// case-insensitive filesystems macOS and Windows use by default, so their
+/// worktree paths are treated as colliding on every platform.
+fn paths_equal_ignoring_case(a: &Path, b: &Path) -> bool {
+    a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
 }
 
-/// Finds the worktree entry that matches a branch.
-fn worktree_for_branch<'a>(
-    worktrees: &'a [WorktreeInfo],
-    branch: &str,
-) -> Option<&'a WorktreeInfo> {
-    worktrees
-        .iter()
-        .find(|wt| wt.branch.as_deref() == Some(branch))
+/// Resolves symlinks in an existing path, returning other paths unchanged.
+///
+/// Git may list a worktree under a symlinked root (such as `/tmp` on macOS)
+/// while recorded visits hold the resolved path, so lookups compare both forms.
+fn canonical_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
 }
 
-/// Finds a remote branch that matches the provided name.
-fn match_remote_branch(branch: &str, remotes: &[String]) -> Option<String> {
-    if remotes.iter().any(|b| b == branch) {
-        return Some(branch.to_string());
+/// Lets the user flatten the directory of a new slashed branch, returning the flat path if chosen.
+///
+/// Only asked with `flat_dir_prompt` set, interactively, and for branches that
+/// do not exist yet; the nested layout stays the default and the branch name
+/// itself keeps its slashes.
+fn choose_new_branch_dir(branch: &str) -> Result<Option<PathBuf>> {
+    if !config::get().flat_dir_prompt
+        || !branch.contains('/')
+        || branch_exists(branch)
+        || !prompts_enabled()
+    {
+        return Ok(None);
     }
+    let nested = worktree_path_for_branch(branch)?;
+    let flat = worktree_path_for_branch(&flatten_branch_dir(branch))?;
+    let items = [
+        format!("{} (nested, like the branch)", nested.display()),
+        format!("{} (flat)", flat.display()),
+    ];
+    let selection = Select::new()
+        .with_prompt(format!("Directory for new branch '{branch}'"))
+        .items(&items)
+        .default(0)
+        .interact()?;
+    Ok((selection == 1).then_some(flat))
+}
 
-    for remote in remotes {
-        if strip_remote_prefix(remote) == branch {
-            return Some(remote.clone());
-        }
+/// Appends `-2`, `-3`, ... to a worktree directory name until `taken` accepts it.
+///
+/// Used when two branches map to the same directory, e.g. `feat/x` flattened
+/// next to an existing `feat-x`; the branch name itself is unchanged. Gives up
+/// after [`MAX_NAME_SUFFIX`] candidates.
+fn suffixed_worktree_path(path: &Path, taken: impl Fn(&Path) -> bool) -> Result<PathBuf> {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match (2..=MAX_NAME_SUFFIX)
+        .map(|n| path.with_file_name(format!("{name}-{n}")))
+        .find(|candidate| !taken(candidate))
+    {
+        Some(candidate) => Ok(candidate),
+        None => anyhow::bail!(
+            "No free directory name next to {} (tried up to -{MAX_NAME_SUFFIX}); pass --path or --dir-name",
+            path.display()
+        ),
     }
+}
 
-    None
+/// Returns true when `path` is a worktree's directory, also when only the case differs.
+///
+/// Case-only differences name the same directory on case-insensitive filesystems.
+fn worktree_path_registered(worktrees: &[WorktreeInfo], path: &Path) -> bool {
+    worktrees
+        .iter()
+        .any(|wt| same_path(&wt.path, path) || paths_equal_ignoring_case(&wt.path, path))
 }
 
-/// Strips the remote prefix from a branch name.
-fn strip_remote_prefix(branch: &str) -> String {
-    branch
-        .split_once('/')
-        .map(|(_, rest)| rest.to_string())
-        .unwrap_or_else(|| branch.to_string())
+/// Replaces the slashes of a branch name so its worktree is a single directory.
+fn flatten_branch_dir(branch: &str) -> String {
+    branch.replace('/', "-")
 }
 
-/// Ensures a branch exists or prompts for creation.
-fn ensure_branch_or_prompt(branch: &str, create: bool, remote: Option<&str>) -> Result<()> {
-    if branch_exists(branch) {
+/// Asks before creating a computed worktree path inside an existing worktree.
+///
+/// Git allows it, but the new checkout then shows up as untracked files of the
+/// enclosing one, which almost always means `WORKTREE_ROOT` is misconfigured.
+fn confirm_path_outside_worktrees(path: &Path) -> Result<()> {
+    let worktrees = list_worktrees_info()?;
+    let Some(enclosing) = enclosing_worktree(path, &worktrees) else {
         return Ok(());
+    };
+    let prompt = format!(
+        "{} is inside the worktree at {}; check WORKTREE_ROOT. Create it anyway?",
+        path.display(),
+        enclosing.path.display()
+    );
+    if !confirm(prompt, false)? {
+        anyhow::bail!(
+            "Refusing to create a worktree inside {}; set WORKTREE_ROOT outside the repository",
+            enclosing.path.display()
+        );
     }
+    Ok(())
+}
 
-    if let Some(remote_ref) = remote
-        && remote_branch_exists(remote_ref)
-    {
-        return Ok(());
+/// Finds the worktree whose directory contains `path`, resolving symlinks where possible.
+fn enclosing_worktree<'a>(path: &Path, worktrees: &'a [WorktreeInfo]) -> Option<&'a WorktreeInfo> {
+    let path = canonicalize_existing_prefix(path);
+    worktrees.iter().find(|wt| {
+        let root = fs::canonicalize(&wt.path).unwrap_or_else(|_| wt.path.clone());
+        path.starts_with(root)
+    })
+}
+
+/// Canonicalizes the longest existing ancestor of `path` and re-appends the rest.
+fn canonicalize_existing_prefix(path: &Path) -> PathBuf {
+    let mut missing = Vec::new();
+    let mut existing = path;
+    loop {
+        if let Ok(resolved) = fs::canonicalize(existing) {
+            return missing
+                .iter()
+                .rev()
+                .fold(resolved, |acc, part| acc.join(part));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
     }
+}
 
-    if create {
-        return Ok(());
+/// Takes the worktree root lock so concurrent gww runs do not race on the same paths.
+///
+/// Locking is best-effort: if the root does not exist yet or the lock file
+/// cannot be used, gww carries on without it. Hold it only around git
+/// mutations, never across prompts.
+fn acquire_root_lock() -> Option<lock::RootLock> {
+    match worktree_root().and_then(|root| lock::RootLock::acquire(&root)) {
+        Ok(lock) => lock,
+        Err(err) => {
+            verbose(format!("Continuing without the worktree lock: {err:#}"));
+            None
+        }
     }
+}
 
-    let should_create = Confirm::new()
-        .with_prompt(format!("Branch '{branch}' does not exist. Create it?"))
-        .default(true)
-        .interact()?;
+/// Decides whether an existing directory at the worktree path should be adopted.
+///
+/// Git refuses to add a worktree over a non-empty directory, so this fails
+/// early with advice unless `--reattach` is given or the user agrees.
+fn should_reattach(args: &CheckoutArgs, path: &Path) -> Result<bool> {
+    match existing_path(path) {
+        ExistingPath::Missing | ExistingPath::EmptyDir => Ok(false),
+        ExistingPath::File => anyhow::bail!(
+            "{} exists and is not a directory; remove it or choose another location with --path or --dir-name",
+            path.display()
+        ),
+        ExistingPath::Checkout => anyhow::bail!(
+            "{} already contains a git checkout; move it away or choose another location with --path or --dir-name",
+            path.display()
+        ),
+        ExistingPath::Occupied => {
+            if args.reattach {
+                return Ok(true);
+            }
+            let prompt = format!(
+                "{} already exists and is not empty. Adopt it as the worktree, keeping its files?",
+                path.display()
+            );
+            if console::Term::stderr().is_term() && confirm(prompt, false)? {
+                return Ok(true);
+            }
+            anyhow::bail!(
+                "{} already exists and is not empty; move it away, choose another location with --path or --dir-name, or pass --reattach to adopt it (its files then show up as changes)",
+                path.display()
+            )
+        }
+    }
+}
 
-    if should_create {
-        Ok(())
+/// Classifies what is at a worktree path before git is asked to create it.
+fn existing_path(path: &Path) -> ExistingPath {
+    if !path.exists() {
+        return ExistingPath::Missing;
+    }
+    if !path.is_dir() {
+        return ExistingPath::File;
+    }
+    if path.join(".git").exists() {
+        return ExistingPath::Checkout;
+    }
+    let is_empty = fs::read_dir(path)
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or(false);
+    if is_empty {
+        ExistingPath::EmptyDir
     } else {
-        anyhow::bail!("Branch '{branch}' does not exist")
+        ExistingPath::Occupied
     }
 }
 
-/// Returns true if the remote branch reference exists.
-fn remote_branch_exists(branch: &str) -> bool {
-    Command::new("git")
-        .args([
-            "show-ref",
-            "--verify",
-            "--quiet",
-            &format!("refs/remotes/{branch}"),
-        ])
-        .status()
-        .map(|status| status.success())
-        .unwrap_or(false)
+/// Registers an existing directory as the worktree for `branch` without touching its files.
+///
+/// Git cannot add a worktree into a non-empty directory, so the worktree is
+/// added empty next to it, its `.git` file is moved over, and `git worktree
+/// repair` points git at the new location. The index is then reset to the
+/// branch so the directory's files show up as changes against it.
+fn reattach_directory(path: &Path, branch: Option<&str>, start_point: Option<&str>) -> Result<()> {
+    if git_version()? < WORKTREE_REPAIR_GIT_VERSION {
+        let GitVersion(major, minor, patch) = WORKTREE_REPAIR_GIT_VERSION;
+        anyhow::bail!("--reattach requires git {major}.{minor}.{patch} or newer");
+    }
+    let name = path
+        .file_name()
+        .context("Worktree path has no directory name")?
+        .to_string_lossy();
+    let staging = path.with_file_name(format!(".{name}.gww-reattach"));
+    git_worktree_add(&staging, branch, start_point, true)?;
+    let moved = fs::rename(staging.join(".git"), path.join(".git"))
+        .with_context(|| format!("Failed to move .git into {}", path.display()));
+    if let Err(err) = moved {
+        let _ = git_worktree_remove(&staging, branch, true);
+        return Err(err);
+    }
+    fs::remove_dir(&staging).with_context(|| format!("Failed to remove {}", staging.display()))?;
+    git_output([
+        OsStr::new("worktree"),
+        OsStr::new("repair"),
+        path.as_os_str(),
+    ])?;
+    git_output([
+        OsStr::new("-C"),
+        path.as_os_str(),
+        OsStr::new("reset"),
+        OsStr::new("--quiet"),
+    ])?;
+    match branch {
+        Some(branch) => eprintln!("Adopted {} as the worktree for '{branch}'", path.display()),
+        None => eprintln!("Adopted {} as a detached worktree", path.display()),
+    }
+    Ok(())
 }
 
-/// Returns true if the local branch reference exists.
-fn branch_exists(branch: &str) -> bool {
-    Command::new("git")
-        .args([
-            "show-ref",
-            "--verify",
-            "--quiet",
-            &format!("refs/heads/{branch}"),
-        ])
-        .status()
-        .map(|status| status.success())
-        .unwrap_or(false)
+/// Adds a worktree for a branch, applies new-worktree setup, and emits its path.
+fn add_worktree(args: &CheckoutArgs, branch: &str, start_point: Option<&str>) -> Result<()> {
+    let path = match create_worktree(args, Some(branch), start_point) {
+        Ok(path) => path,
+        Err(err) => {
+            return match err.downcast_ref::<CheckedOutElsewhere>() {
+                Some(elsewhere) if console::Term::stderr().is_term() => {
+                    resolve_checked_out_elsewhere(args, branch, &elsewhere.path)
+                }
+                _ => Err(err),
+            };
+        }
+    };
+    emit_cd(&path, Some(branch));
+    Ok(())
 }
 
-/// Builds the target worktree path for a branch.
-fn worktree_path_for_branch(branch: &str) -> Result<PathBuf> {
-    let root = worktree_root()?;
-    let repo = repo_name_stem()?;
-    Ok(root.join(repo).join(branch))
+/// Asks what to do when git reports the branch is checked out in another worktree.
+fn resolve_checked_out_elsewhere(args: &CheckoutArgs, branch: &str, path: &Path) -> Result<()> {
+    let items = [
+        format!("Go to the existing worktree at {}", path.display()),
+        format!("Create a new branch from '{branch}' instead"),
+        "Abort".to_string(),
+    ];
+    let selection = Select::new()
+        .with_prompt(format!(
+            "'{branch}' is already checked out at {}",
+            path.display()
+        ))
+        .items(&items)
+        .default(0)
+        .interact_opt()?;
+    match selection {
+        Some(0) => {
+            emit_cd(path, Some(branch));
+            Ok(())
+        }
+        Some(1) => {
+            let name: String = Input::new()
+                .with_prompt("New branch name")
+                .interact_text()?;
+            let name = new_branch_name(args, name.trim());
+            create_branch_worktree(args, &name, Some(branch))
+        }
+        _ => anyhow::bail!("Checkout cancelled"),
+    }
 }
 
-/// Resolves the configured root for worktrees.
-fn worktree_root() -> Result<PathBuf> {
-    if let Ok(root) = env::var("WORKTREE_ROOT") {
-        return Ok(PathBuf::from(root));
+/// Extracts the other worktree's path from git's "already checked out" refusal.
+///
+/// Git has phrased this as `is already checked out at '<path>'` and, since
+/// 2.42, `is already used by worktree at '<path>'`.
+fn checked_out_path(stderr: &str) -> Option<PathBuf> {
+    stderr.lines().find_map(|line| {
+        let (_, rest) = line
+            .split_once("is already checked out at '")
+            .or_else(|| line.split_once("is already used by worktree at '"))?;
+        rest.strip_suffix('\'').map(PathBuf::from)
+    })
+}
+
+/// Emits a detached worktree at `commit`, creating one named after its short SHA if needed.
+fn checkout_detached(args: &CheckoutArgs) -> Result<()> {
+    let commit = args.branch.as_deref().context("--detach needs a commit")?;
+    let sha = git_output([
+        "rev-parse",
+        "--verify",
+        "--quiet",
+        &format!("{commit}^{{commit}}"),
+    ])
+    .map(|output| output.trim().to_string())
+    .map_err(|_| anyhow::anyhow!("'{commit}' does not name a commit"))?;
+    let worktrees = list_worktrees_info()?;
+    if args.path.is_none()
+        && let Some(existing) = worktrees
+            .iter()
+            .find(|wt| is_detached(wt) && wt.head.as_deref() == Some(sha.as_str()))
+        && existing.path.exists()
+    {
+        return reuse_worktree(args, existing);
     }
-    let home = env::var("HOME").context("HOME not set")?;
-    Ok(PathBuf::from(home).join("devel").join("worktrees"))
+    let path = create_worktree(args, None, Some(&sha))?;
+    emit_cd(&path, None);
+    Ok(())
 }
 
-/// Resolves the repository name for worktree paths.
-fn repo_name_stem() -> Result<String> {
-    if let Ok(url) = git_output(["remote", "get-url", "origin"])
-        && let Some(stem) = repo_name_from_url(url.trim())
+/// Adds a worktree for a branch and applies new-worktree setup, returning its path.
+///
+/// Without a branch the worktree is detached at `start_point` and named after
+/// its short SHA.
+fn create_worktree(
+    args: &CheckoutArgs,
+    branch: Option<&str>,
+    start_point: Option<&str>,
+) -> Result<PathBuf> {
+    let mut custom_dir = args.dir_name.is_some();
+    let mut path = match (args.path.as_deref(), args.dir_name.as_deref()) {
+        (Some(path), _) => std::path::absolute(path)
+            .with_context(|| format!("Failed to resolve {}", path.display()))?,
+        (None, Some(dir_name)) => worktree_path_for_branch(dir_name)?,
+        (None, None) => match branch {
+            Some(branch) => match choose_new_branch_dir(branch)? {
+                Some(flat) => {
+                    custom_dir = true;
+                    flat
+                }
+                None => worktree_path_for_branch(branch)?,
+            },
+            None => {
+                let commit = start_point.context("a detached worktree needs a commit")?;
+                worktree_path_for_branch(short_sha(commit))?
+            }
+        },
+    };
+    if args.path.is_none()
+        && args.dir_name.is_none()
+        && let Some(branch) = branch
     {
-        return Ok(stem);
+        let worktrees = list_worktrees_info()?;
+        if worktree_path_registered(&worktrees, &path) {
+            let unique = suffixed_worktree_path(&path, |candidate| {
+                worktree_path_registered(&worktrees, candidate) || candidate.exists()
+            })?;
+            match worktrees.iter().find(|wt| {
+                !same_path(&wt.path, &path) && paths_equal_ignoring_case(&wt.path, &path)
+            }) {
+                Some(other) => eprintln!(
+                    "{} differs only in case from the worktree at {}, which is the same directory on case-insensitive filesystems; using {} for '{branch}'",
+                    path.display(),
+                    other.path.display(),
+                    unique.display()
+                ),
+                None => eprintln!(
+                    "{} is already another branch's worktree; using {} for '{branch}'",
+                    path.display(),
+                    unique.display()
+                ),
+            }
+            path = unique;
+            custom_dir = true;
+        }
+    }
+    if args.path.is_none() {
+        confirm_path_outside_worktrees(&path)?;
+    }
+    if args.no_create_dirs
+        && let Some(parent) = path.parent()
+        && !parent.is_dir()
+    {
+        anyhow::bail!(
+            "Parent directory {} does not exist; check WORKTREE_ROOT or drop --no-create-dirs",
+            parent.display()
+        );
+    }
+    let reattach = should_reattach(args, &path)?;
+    {
+        let _lock = acquire_root_lock();
+        if reattach {
+            reattach_directory(&path, branch, start_point)?;
+        } else {
+            git_worktree_add(&path, branch, start_point, args.no_checkout)?;
+        }
+    }
+    if custom_dir
+        && let Some(branch) = branch
+        && let Err(err) = state::save_worktree_dir(branch, &path)
+    {
+        warn(format!("failed to record worktree directory: {err:#}"));
+    }
+    apply_worktree_config(&path);
+    let patterns = sparse_patterns(args);
+    if let Err(err) = apply_sparse_checkout(&path, &patterns) {
+        warn(format!("sparse-checkout setup failed: {err:#}"));
+    }
+    if let Some(reason) = args.lock.as_deref()
+        && let Err(err) = lock_worktree(&path, reason)
+    {
+        warn(format!("failed to lock worktree: {err:#}"));
+    }
+    if args.no_checkout {
+        // Submodules and the template expect checked-out files.
+        eprintln!(
+            "Files were not checked out; run `git checkout` in {} to populate it",
+            path.display()
+        );
+        return Ok(path);
+    }
+    if should_init_submodules(args) {
+        if path.join(".gitmodules").is_file() {
+            eprintln!("Initializing submodules in {}", path.display());
+            if let Err(err) = init_submodules(&path) {
+                warn(format!("submodule initialization failed: {err:#}"));
+            }
+        } else {
+            verbose("No .gitmodules in the new worktree; skipping submodules".to_string());
+        }
+    }
+    if let Some(template) = config::get().template.as_deref() {
+        verbose(format!("Copying template {}", template.display()));
+        if let Err(err) = copy_template(template, &path, args.overwrite_template) {
+            warn(format!("template copy failed: {err:#}"));
+        }
+    }
+    Ok(path)
+}
+
+/// Prints the git worktree list, marking the main worktree and missing directories.
+fn list_worktrees(args: ListArgs) -> Result<()> {
+    let mut worktrees = list_worktrees_info()?;
+    if args.count {
+        println!("{}", worktree_count(&worktrees, args.exclude_main));
+        return Ok(());
+    }
+    if args.available {
+        return list_available_branches(&worktrees);
+    }
+    let mut order: Vec<usize> = (0..worktrees.len()).collect();
+    if let Some(sort) = args.sort {
+        let meta = if sort == ListSort::Recent {
+            worktree_branch_metadata(&worktrees, false)?
+        } else {
+            HashMap::new()
+        };
+        order = worktree_order(&worktrees, sort, &meta);
+        worktrees = order
+            .iter()
+            .map(|&index| worktrees[index].clone())
+            .collect();
+    }
+    if args.porcelain {
+        return list_worktrees_porcelain(&worktrees);
+    }
+    if args.json {
+        return list_worktrees_json(&worktrees);
+    }
+    if let Some(template) = args.format.as_deref() {
+        let parts = parse_list_format(template)?;
+        let track_info = parts.iter().any(|part| {
+            matches!(
+                part,
+                FormatPart::Field(ListField::Ahead | ListField::Behind)
+            )
+        });
+        let meta = worktree_branch_metadata(&worktrees, track_info)?;
+        for worktree in &worktrees {
+            let branch_meta = worktree.branch.as_ref().and_then(|branch| meta.get(branch));
+            println!("{}", render_list_format(&parts, worktree, branch_meta));
+        }
+        return Ok(());
+    }
+    let output = git_output(["worktree", "list"])?;
+    let lines: Vec<&str> = output.lines().collect();
+    let output = if lines.len() == order.len() {
+        order
+            .iter()
+            .map(|&index| format!("{}\n", lines[index]))
+            .collect()
+    } else {
+        output
+    };
+    print!("{}", annotate_worktree_list(&output, &worktrees));
+    let prunable = worktrees.iter().filter(|wt| wt.prunable.is_some()).count();
+    if prunable > 0 {
+        eprintln!(
+            "{prunable} prunable worktree(s); run `git worktree prune`, or `gww remove <branch>` for one"
+        );
+    }
+    Ok(())
+}
+
+/// Returns the indices of `worktrees` in `list --sort` order.
+///
+/// Detached and bare worktrees have no branch and sort last by path for
+/// `recent` and `alpha`; branches without commit metadata follow the dated ones.
+fn worktree_order(
+    worktrees: &[WorktreeInfo],
+    sort: ListSort,
+    meta: &HashMap<String, BranchMeta>,
+) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..worktrees.len()).collect();
+    let timestamp = |wt: &WorktreeInfo| {
+        wt.branch
+            .as_ref()
+            .and_then(|branch| meta.get(branch))
+            .map(|meta| meta.timestamp_unix)
+    };
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&worktrees[a], &worktrees[b]);
+        let by_path = || a.path.cmp(&b.path);
+        match sort {
+            ListSort::Path => by_path(),
+            ListSort::Alpha => match (&a.branch, &b.branch) {
+                (Some(x), Some(y)) => x.cmp(y),
+                (x, y) => x.is_none().cmp(&y.is_none()).then_with(by_path),
+            },
+            ListSort::Recent => b
+                .branch
+                .is_some()
+                .cmp(&a.branch.is_some())
+                .then_with(|| timestamp(b).cmp(&timestamp(a)))
+                .then_with(|| a.branch.cmp(&b.branch))
+                .then_with(by_path),
+        }
+    });
+    order
+}
+
+/// Splits a `--format` template into literal text and `{placeholder}` fields.
+fn parse_list_format(template: &str) -> Result<Vec<FormatPart>> {
+    let mut parts: Vec<FormatPart> = Vec::new();
+    for part in template_parts(template).context("Invalid --format template")? {
+        let field = match part {
+            TemplatePart::Literal(text) => {
+                match parts.last_mut() {
+                    Some(FormatPart::Literal(previous)) => previous.push_str(text),
+                    _ => parts.push(FormatPart::Literal(text.to_string())),
+                }
+                continue;
+            }
+            TemplatePart::Placeholder("path") => ListField::Path,
+            TemplatePart::Placeholder("branch") => ListField::Branch,
+            TemplatePart::Placeholder("subject") => ListField::Subject,
+            TemplatePart::Placeholder("author") => ListField::Author,
+            TemplatePart::Placeholder("time") => ListField::Time,
+            TemplatePart::Placeholder("ahead") => ListField::Ahead,
+            TemplatePart::Placeholder("behind") => ListField::Behind,
+            TemplatePart::Placeholder(name) => {
+                return Err(unknown_placeholder(
+                    name,
+                    "--format",
+                    &[
+                        "path", "branch", "subject", "author", "time", "ahead", "behind",
+                    ],
+                ));
+            }
+        };
+        parts.push(FormatPart::Field(field));
+    }
+    Ok(parts)
+}
+
+/// Renders one worktree with a parsed `--format` template.
+///
+/// Fields without a value (detached HEAD, no upstream) render as empty strings.
+fn render_list_format(
+    parts: &[FormatPart],
+    worktree: &WorktreeInfo,
+    meta: Option<&BranchMeta>,
+) -> String {
+    let summary = meta.map(|meta| &meta.summary);
+    let counts = match summary.and_then(|summary| summary.track) {
+        Some(UpstreamTrack::Counts { ahead, behind }) => Some((ahead, behind)),
+        _ => None,
+    };
+    parts
+        .iter()
+        .map(|part| match part {
+            FormatPart::Literal(text) => text.clone(),
+            FormatPart::Field(ListField::Path) => worktree.path.display().to_string(),
+            FormatPart::Field(ListField::Branch) => worktree.branch.clone().unwrap_or_default(),
+            FormatPart::Field(ListField::Subject) => summary
+                .map(|summary| summary.subject.clone())
+                .unwrap_or_default(),
+            FormatPart::Field(ListField::Author) => summary
+                .map(|summary| summary.author.clone())
+                .unwrap_or_default(),
+            FormatPart::Field(ListField::Time) => summary
+                .map(|summary| summary.timestamp_label.clone())
+                .unwrap_or_default(),
+            FormatPart::Field(ListField::Ahead) => counts
+                .map(|(ahead, _)| ahead.to_string())
+                .unwrap_or_default(),
+            FormatPart::Field(ListField::Behind) => counts
+                .map(|(_, behind)| behind.to_string())
+                .unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Streams a JSON object per worktree as soon as its status is known.
+///
+/// A worktree whose status cannot be read gets null counts and an `error`
+/// message instead of stopping the listing.
+fn list_worktrees_porcelain(worktrees: &[WorktreeInfo]) -> Result<()> {
+    for worktree in worktrees {
+        let path = worktree.path.display().to_string();
+        let (counts, error) = match worktree_status_counts(&worktree.path) {
+            Ok(counts) => (Some(counts), None),
+            Err(err) => (None, Some(format!("{err:#}"))),
+        };
+        let count = |pick: fn(&StatusCounts) -> u32| {
+            counts.map_or(jsonl::Value::Null, |counts| {
+                jsonl::Value::Int(i64::from(pick(&counts)))
+            })
+        };
+        jsonl::emit(&jsonl::object(&[
+            ("branch", worktree.branch.as_deref().into()),
+            ("path", jsonl::Value::Str(&path)),
+            ("head", worktree.head.as_deref().into()),
+            ("main", jsonl::Value::Bool(worktree.is_main)),
+            ("detached", jsonl::Value::Bool(worktree.detached)),
+            ("locked", worktree.locked.as_deref().into()),
+            ("prunable", worktree.prunable.as_deref().into()),
+            ("staged", count(|counts| counts.staged)),
+            ("unstaged", count(|counts| counts.unstaged)),
+            ("untracked", count(|counts| counts.untracked)),
+            ("error", error.as_deref().into()),
+        ]))?;
+    }
+    Ok(())
+}
+
+/// Prints one JSON array summarizing each worktree's health for dashboards.
+///
+/// `ahead`/`behind` are null without an upstream (or when it is gone); a
+/// worktree whose status cannot be read gets null fields and an `error`.
+fn list_worktrees_json(worktrees: &[WorktreeInfo]) -> Result<()> {
+    let meta = worktree_branch_metadata(worktrees, true)?;
+    let items: Vec<String> = worktrees
+        .iter()
+        .map(|worktree| {
+            let path = worktree.path.display().to_string();
+            let (counts, error) = match worktree_status_counts(&worktree.path) {
+                Ok(counts) => (Some(counts), None),
+                Err(err) => (None, Some(format!("{err:#}"))),
+            };
+            let track = worktree
+                .branch
+                .as_deref()
+                .and_then(|branch| meta.get(branch))
+                .and_then(|meta| meta.summary.track);
+            let (ahead, behind) = match track {
+                Some(UpstreamTrack::Counts { ahead, behind }) => (
+                    jsonl::Value::Int(i64::from(ahead)),
+                    jsonl::Value::Int(i64::from(behind)),
+                ),
+                _ => (jsonl::Value::Null, jsonl::Value::Null),
+            };
+            jsonl::object(&[
+                ("branch", worktree.branch.as_deref().into()),
+                ("path", jsonl::Value::Str(&path)),
+                (
+                    "dirty",
+                    counts.map_or(jsonl::Value::Null, |counts| {
+                        jsonl::Value::Bool(counts.staged + counts.unstaged > 0)
+                    }),
+                ),
+                (
+                    "untracked",
+                    counts.map_or(jsonl::Value::Null, |counts| {
+                        jsonl::Value::Int(i64::from(counts.untracked))
+                    }),
+                ),
+                ("ahead", ahead),
+                ("behind", behind),
+                ("error", error.as_deref().into()),
+            ])
+        })
+        .collect();
+    jsonl::emit(&jsonl::array(&items))
+}
+
+/// Counts staged, unstaged, and untracked paths in a worktree.
+fn worktree_status_counts(path: &Path) -> Result<StatusCounts> {
+    if !path.is_dir() {
+        anyhow::bail!("worktree directory is missing");
+    }
+    let output = git_command()
+        .arg("-C")
+        .arg(path)
+        .args(["status", "--porcelain"])
+        .output()
+        .map_err(git_spawn_error)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!(
+            "{}",
+            stderr.lines().next().unwrap_or("git status failed").trim()
+        );
+    }
+    Ok(parse_status_counts(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// Tallies `git status --porcelain` lines; a path staged and modified counts in both.
+fn parse_status_counts(output: &str) -> StatusCounts {
+    let mut counts = StatusCounts::default();
+    for line in output.lines() {
+        let mut codes = line.chars();
+        match (codes.next(), codes.next()) {
+            (Some('?'), Some('?')) => counts.untracked += 1,
+            (Some(index), Some(tree)) => {
+                if index != ' ' {
+                    counts.staged += 1;
+                }
+                if tree != ' ' {
+                    counts.unstaged += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    counts
+}
+
+/// Prints the selector's local and remote entries, i.e. branches without a worktree.
+fn list_available_branches(worktrees: &[WorktreeInfo]) -> Result<()> {
+    let options = CandidateOptions {
+        ignore: ignore_patterns(),
+        age_color: config::get().age_color,
+        sources: AVAILABLE_SOURCES.to_vec(),
+        ..CandidateOptions::default()
+    };
+    let candidates = build_branch_candidates(
+        worktrees,
+        &list_local_branches()?,
+        &list_remote_branches()?,
+        &options,
+    )?;
+    for candidate in candidates {
+        println!("{}", format_branch_item(&candidate, &options));
+    }
+    Ok(())
+}
+
+/// Prints the selector's entries or bare names without prompting, optionally limited to some sources.
+fn list_branches(sources: &[BranchSource], format: BranchesFormat) -> Result<()> {
+    ensure_git_repo()?;
+    let worktrees = list_worktrees_info()?;
+    let options = CandidateOptions {
+        sources: sources.to_vec(),
+        ..candidate_options(&CheckoutArgs::default())?
+    };
+    // Locals are still needed with remotes to hide remote branches shadowed by them.
+    let locals = if wants_source(sources, BranchSource::Local)
+        || wants_source(sources, BranchSource::Remote)
+    {
+        list_local_branches()?
+    } else {
+        Vec::new()
+    };
+    let remotes = if wants_source(sources, BranchSource::Remote) {
+        list_remote_branches()?
+    } else {
+        Vec::new()
+    };
+    let candidates = build_branch_candidates(&worktrees, &locals, &remotes, &options)?;
+    for candidate in candidates {
+        match format {
+            BranchesFormat::Selector => println!("{}", format_branch_item(&candidate, &options)),
+            BranchesFormat::Names => println!("{}", candidate.name),
+        }
+    }
+    Ok(())
+}
+
+/// Keeps candidates from the given sources; an empty list keeps them all.
+fn filter_by_source(candidates: Vec<BranchInfo>, sources: &[BranchSource]) -> Vec<BranchInfo> {
+    candidates
+        .into_iter()
+        .filter(|candidate| wants_source(sources, candidate.source))
+        .collect()
+}
+
+/// Returns true when `source` is among `sources`, or when no sources were given.
+fn wants_source(sources: &[BranchSource], source: BranchSource) -> bool {
+    sources.is_empty() || sources.contains(&source)
+}
+
+/// Counts worktrees, optionally skipping the main worktree.
+fn worktree_count(worktrees: &[WorktreeInfo], exclude_main: bool) -> usize {
+    worktrees
+        .iter()
+        .filter(|wt| !(exclude_main && wt.is_main))
+        .count()
+}
+
+/// Appends `(main)` and `(missing)` markers to raw list lines.
+///
+/// Raw and porcelain listings share the same order, so lines are matched by
+/// position; the raw output is returned untouched if the counts disagree.
+fn annotate_worktree_list(raw: &str, worktrees: &[WorktreeInfo]) -> String {
+    let lines: Vec<&str> = raw.lines().collect();
+    if lines.len() != worktrees.len() {
+        return raw.to_string();
+    }
+    lines
+        .iter()
+        .zip(worktrees)
+        .map(|(line, worktree)| {
+            let main = if worktree.is_main { " (main)" } else { "" };
+            match worktree.prunable.as_deref() {
+                // git ends prunable lines with a bare `prunable`; the reason replaces it.
+                Some(reason) => {
+                    let line = line.strip_suffix(" prunable").unwrap_or(line);
+                    let note = if reason.is_empty() {
+                        " (prunable)".to_string()
+                    } else {
+                        format!(" (prunable: {reason})")
+                    };
+                    format!("{line}{main}{note}\n")
+                }
+                None if !worktree.path.exists() => format!("{line}{main} (missing)\n"),
+                None => format!("{line}{main}\n"),
+            }
+        })
+        .collect()
+}
+
+/// Measures branch candidate building time, broken down by phase.
+fn timechooser(format: TimingFormat) -> Result<()> {
+    ensure_git_repo()?;
+    let start = Instant::now();
+    let mut phases: Vec<(&str, Duration)> = Vec::new();
+
+    let phase_start = Instant::now();
+    let worktrees = list_worktrees_info()?;
+    phases.push(("worktrees", phase_start.elapsed()));
+
+    let phase_start = Instant::now();
+    let local_branches = list_local_branches()?;
+    phases.push(("local", phase_start.elapsed()));
+
+    let phase_start = Instant::now();
+    let remote_branches = list_remote_branches()?;
+    phases.push(("remote", phase_start.elapsed()));
+
+    let phase_start = Instant::now();
+    let meta = batch_branch_metadata(false)?;
+    let current = current_branch()?;
+    phases.push(("metadata", phase_start.elapsed()));
+
+    let phase_start = Instant::now();
+    let candidates = assemble_branch_candidates(
+        &worktrees,
+        &local_branches,
+        &remote_branches,
+        &CandidateOptions {
+            ignore: ignore_patterns(),
+            current_first: true,
+            ..CandidateOptions::default()
+        },
+        &meta,
+        current.as_deref(),
+    );
+    phases.push(("sorting", phase_start.elapsed()));
+    let elapsed = start.elapsed();
+
+    match format {
+        TimingFormat::Human => {
+            println!(
+                "Built {} branch entries in {:.2?}",
+                candidates.len(),
+                elapsed
+            );
+            for (name, duration) in &phases {
+                println!("  {name:<10} {duration:.2?}");
+            }
+        }
+        TimingFormat::Tsv => {
+            let mut fields = vec![candidates.len().to_string(), format_millis(elapsed)];
+            fields.extend(phases.iter().map(|(_, duration)| format_millis(*duration)));
+            println!("{}", fields.join("\t"));
+        }
+    }
+    Ok(())
+}
+
+/// Formats a duration as fractional milliseconds.
+fn format_millis(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64() * 1000.0)
+}
+
+/// Removes the selected worktree from disk.
+fn remove_worktree(
+    branch: Option<String>,
+    force: bool,
+    include_main: bool,
+    delete_branch: bool,
+) -> Result<()> {
+    let branch = trimmed_branch_arg(branch)?;
+    ensure_git_repo()?;
+    let worktrees = list_worktrees_info()?;
+    let worktree = match branch.as_deref() {
+        Some(branch) => pick_worktree_for_branch(&worktrees, branch)?
+            .or_else(|| recorded_worktree(&worktrees, branch))
+            .cloned()
+            .with_context(|| format!("No worktree found for branch '{branch}'"))?,
+        None => select_worktree(&worktrees, include_main)?,
+    };
+    if !worktree.path.exists() {
+        eprintln!(
+            "Worktree directory {} is missing; removing the stale entry",
+            worktree.path.display()
+        );
+        remove_missing_worktree(&worktree.path)?;
+    } else {
+        git_worktree_remove(&worktree.path, worktree.branch.as_deref(), force)?;
+    }
+    let branch = branch.as_deref().or(worktree.branch.as_deref());
+    record_removal(&worktree, branch);
+    if let Some(branch) = branch
+        && let Err(err) = state::forget_worktree_dir(branch)
+    {
+        warn(format!(
+            "failed to update worktree directory records: {err:#}"
+        ));
+    }
+    if delete_branch {
+        match branch {
+            Some(branch) => delete_local_branch(branch, force)?,
+            None => warn("the removed worktree was detached; no branch to delete".to_string()),
+        }
+    }
+    Ok(())
+}
+
+/// Trims a branch argument, rejecting one that is empty or only whitespace.
+///
+/// A misfired script such as `gww co "$unset"` would otherwise reach git
+/// with an empty ref.
+fn trimmed_branch_arg(branch: Option<String>) -> Result<Option<String>> {
+    let Some(branch) = branch else {
+        return Ok(None);
+    };
+    let trimmed = branch.trim();
+    if trimmed.is_empty() {
+        anyhow::bail!(
+            "Branch name is empty; pass a branch name, or none to pick one interactively"
+        );
+    }
+    Ok(Some(trimmed.to_string()))
+}
+
+/// Remembers a removed worktree so `gww undo` can recreate it.
+fn record_removal(worktree: &WorktreeInfo, branch: Option<&str>) {
+    let Some(head) = worktree.head.clone() else {
+        return;
+    };
+    let removed = state::RemovedWorktree {
+        branch: branch.unwrap_or_default().to_string(),
+        path: worktree.path.clone(),
+        head,
+        removed_unix: unix_now(),
+    };
+    if let Err(err) = state::save_last_removed(&removed) {
+        warn(format!("failed to record the removal for undo: {err:#}"));
+    }
+}
+
+/// Recreates the most recently removed worktree at its old path.
+///
+/// The branch is checked out again; if it was deleted it is recreated at the
+/// commit it pointed to, and detached worktrees get that commit back.
+/// Uncommitted changes went with the directory and cannot be restored.
+fn undo_remove() -> Result<()> {
+    ensure_git_repo()?;
+    let removed = state::load_last_removed()?
+        .context("Nothing to undo; no worktree removal has been recorded")?;
+    let path = &removed.path;
+    if !matches!(
+        existing_path(path),
+        ExistingPath::Missing | ExistingPath::EmptyDir
+    ) {
+        anyhow::bail!(
+            "Cannot restore the worktree: {} already exists",
+            path.display()
+        );
+    }
+    let branch = (!removed.branch.is_empty()).then_some(removed.branch.as_str());
+    if let Some(branch) = branch
+        && let Some(existing) = worktree_for_branch(&list_worktrees_info()?, branch)
+    {
+        anyhow::bail!(
+            "Branch '{branch}' is already checked out at {}",
+            existing.path.display()
+        );
+    }
+    let start_point = match branch {
+        Some(branch) if branch_exists(branch) => None,
+        Some(branch) => {
+            eprintln!(
+                "Branch '{branch}' no longer exists; recreating it at {}",
+                short_sha(&removed.head)
+            );
+            Some(removed.head.as_str())
+        }
+        None => Some(removed.head.as_str()),
+    };
+    {
+        let _lock = acquire_root_lock();
+        git_worktree_add(path, branch, start_point, false)?;
+    }
+    warn(
+        "only committed work is restored; uncommitted changes in the removed worktree are lost"
+            .to_string(),
+    );
+    if let Err(err) = state::forget_last_removed() {
+        verbose(format!("Could not clear the removal record: {err:#}"));
+    }
+    emit_cd(path, branch);
+    Ok(())
+}
+
+/// Deletes a branch with `git branch -d`, confirming before `-D` on an unmerged branch.
+///
+/// Without `force` an unmerged branch is kept and only reported.
+fn delete_local_branch(branch: &str, force: bool) -> Result<()> {
+    let output = git_command()
+        .args(["branch", "-d", branch])
+        .output()
+        .map_err(git_spawn_error)?;
+    if output.status.success() {
+        eprintln!("Deleted branch '{branch}'");
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let message = stderr
+        .lines()
+        .next()
+        .unwrap_or("git branch -d failed")
+        .trim_start_matches("error: ")
+        .trim_end_matches('.');
+    if !force {
+        warn(format!(
+            "kept branch '{branch}': {message}; rerun with --force to delete it anyway"
+        ));
+        return Ok(());
+    }
+    let prompt = format!("Branch '{branch}' is not fully merged. Delete it anyway?");
+    if !confirm(prompt, false)? {
+        eprintln!("Kept branch '{branch}'");
+        return Ok(());
+    }
+    git_output(["branch", "-D", branch])
+        .with_context(|| format!("Failed to delete branch '{branch}'"))?;
+    eprintln!("Deleted branch '{branch}'");
+    Ok(())
+}
+
+/// Finds a worktree through the directory recorded by `checkout --dir-name`.
+///
+/// This covers worktrees whose HEAD no longer names the branch, such as after
+/// detaching it.
+fn recorded_worktree<'a>(worktrees: &'a [WorktreeInfo], branch: &str) -> Option<&'a WorktreeInfo> {
+    let path = state::load_worktree_dir(branch).ok().flatten()?;
+    worktrees.iter().find(|wt| same_path(&wt.path, &path))
+}
+
+/// Checks that a `--dir-name` value is a single, ordinary path component.
+fn validate_dir_name(name: &str) -> Result<&str> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(std::path::Component::Normal(_)), None) => Ok(name),
+        _ => anyhow::bail!("--dir-name must be a single directory name, got '{name}'"),
+    }
+}
+
+/// Prints the resolved `<worktree root>/<repo>` directory for the current repository.
+fn print_root() -> Result<()> {
+    ensure_git_repo()?;
+    match env::var("WORKTREE_ROOT") {
+        Ok(root) => verbose(format!("Worktree root from WORKTREE_ROOT={root}")),
+        Err(_) => verbose("WORKTREE_ROOT is not set; using ~/devel/worktrees".to_string()),
+    }
+    let repo = repo_name_stem()?;
+    verbose(format!("Repository name: {repo}"));
+    if let Some(layout) = worktree_layout() {
+        verbose(format!("Worktree layout: {layout}"));
+        let dir = expand_worktree_layout(layout_repo_dir(&layout), "")?;
+        println!("{}", dir.display());
+        return Ok(());
+    }
+    println!("{}", worktree_root()?.join(repo).display());
+    Ok(())
+}
+
+/// Prints shell functions that auto-cd into worktrees.
+fn autocd() -> Result<()> {
+    print!("{}", autocd_script(&cd_prefix()));
+    Ok(())
+}
+
+/// Builds the auto-cd shell functions for the given cd marker prefix.
+///
+/// The prefix is passed to every `gww` the functions run, so the marker gww
+/// prints always matches the one the functions grep for.
+fn autocd_script(prefix: &str) -> String {
+    format!(
+        "gww() {{\n    local output\n    output=$({env} command gww \"$@\")\n    local exit_code=$?\n    printf '%s\\n' \"$output\" | grep -v {err_pattern}\n    _gww_warnings \"$output\"\n    local cd_path\n    cd_path=$(printf '%s\\n' \"$output\" | grep {pattern} | cut -c{start}-)\n    [ -n \"$cd_path\" ] && cd -- \"$cd_path\"\n    return $exit_code\n}}\n\n_gww_cd() {{\n    local output\n    output=$({env} command gww checkout \"$@\")\n    local exit_code=$?\n    _gww_warnings \"$output\"\n    if [ $exit_code -ne 0 ]; then\n        printf '%s\\n' \"$output\" | grep -v {err_pattern}\n        return $exit_code\n    fi\n    local cd_path\n    cd_path=$(printf '%s\\n' \"$output\" | grep {pattern} | cut -c{start}-)\n    [ -n \"$cd_path\" ] && cd -- \"$cd_path\"\n}}\n\n_gww_warnings() {{\n    printf '%s\\n' \"$1\" | grep {err_pattern} | cut -c{err_start}- | sed 's/^/Warning: /' >&2\n}}\n",
+        env = format!(
+            "GWW_SHELL_INTEGRATION=1 GWW_CD_PREFIX={}",
+            shell_quote(prefix)
+        ),
+        pattern = grep_prefix_pattern(prefix),
+        start = prefix.len() + 1,
+        err_pattern = grep_prefix_pattern(ERR_PREFIX),
+        err_start = ERR_PREFIX.len() + 1
+    )
+}
+
+/// Builds a shell-quoted grep pattern that matches lines starting with `prefix`.
+fn grep_prefix_pattern(prefix: &str) -> String {
+    let mut pattern = String::from("^");
+    for ch in prefix.chars() {
+        if matches!(ch, '\\' | '.' | '[' | ']' | '*' | '^' | '$') {
+            pattern.push('\\');
+        }
+        pattern.push(ch);
+    }
+    format!("'{}'", pattern.replace('\'', "'\\''"))
+}
+
+/// Resolves the marker prefix used for auto-cd output.
+fn cd_prefix() -> String {
+    env::var("GWW_CD_PREFIX")
+        .ok()
+        .filter(|prefix| !prefix.is_empty())
+        .unwrap_or_else(|| CD_PREFIX.to_string())
+}
+
+/// Prints a health report for git, the worktree root, and shell integration.
+fn doctor() -> Result<()> {
+    let checks = [
+        check_git_version(),
+        check_git_repo(),
+        check_worktree_root(),
+        check_shell_integration(),
+    ];
+    for check in &checks {
+        println!("{}", format_check(check));
+    }
+
+    let failures = checks
+        .iter()
+        .filter(|check| check.status == CheckStatus::Fail)
+        .count();
+    if failures > 0 {
+        anyhow::bail!("{failures} check(s) failed");
+    }
+    Ok(())
+}
+
+/// Formats a doctor check as a colored status line.
+fn format_check(check: &CheckResult) -> String {
+    let label = match check.status {
+        CheckStatus::Pass => style("PASS").green(),
+        CheckStatus::Warn => style("WARN").yellow(),
+        CheckStatus::Fail => style("FAIL").red(),
+    };
+    format!("[{}] {}: {}", label.bold(), check.name, check.detail)
+}
+
+/// Checks that git is installed and supports the worktree commands gww uses.
+fn check_git_version() -> CheckResult {
+    let name = "git";
+    let version = match git_version() {
+        Ok(version) => version,
+        Err(err) => {
+            return CheckResult {
+                status: CheckStatus::Fail,
+                name,
+                detail: format!("{err:#}"),
+            };
+        }
+    };
+    let GitVersion(major, minor, patch) = version;
+    if version >= MIN_GIT_VERSION {
+        CheckResult {
+            status: CheckStatus::Pass,
+            name,
+            detail: format!("version {major}.{minor}.{patch}"),
+        }
+    } else {
+        let GitVersion(min_major, min_minor, min_patch) = MIN_GIT_VERSION;
+        CheckResult {
+            status: CheckStatus::Warn,
+            name,
+            detail: format!(
+                "version {major}.{minor}.{patch} is older than {min_major}.{min_minor}.{min_patch}"
+            ),
+        }
+    }
+}
+
+/// Checks that the current directory is inside a git repository.
+fn check_git_repo() -> CheckResult {
+    let name = "repository";
+    match git_output(["rev-parse", "--show-toplevel"]) {
+        Ok(root) => CheckResult {
+            status: CheckStatus::Pass,
+            name,
+            detail: root.trim().to_string(),
+        },
+        Err(err) => CheckResult {
+            status: CheckStatus::Fail,
+            name,
+            detail: format!("not a git repository ({err:#})"),
+        },
+    }
+}
+
+/// Checks that the worktree root resolves and can be written to.
+fn check_worktree_root() -> CheckResult {
+    let name = "worktree root";
+    let root = match worktree_root() {
+        Ok(root) => root,
+        Err(err) => {
+            return CheckResult {
+                status: CheckStatus::Fail,
+                name,
+                detail: format!("{err:#}"),
+            };
+        }
+    };
+    let Some(existing) = root.ancestors().find(|ancestor| ancestor.exists()) else {
+        return CheckResult {
+            status: CheckStatus::Fail,
+            name,
+            detail: format!("{} has no existing parent", root.display()),
+        };
+    };
+    if !is_writable_dir(existing) {
+        return CheckResult {
+            status: CheckStatus::Fail,
+            name,
+            detail: format!("{} is not writable", existing.display()),
+        };
+    }
+    if existing == root {
+        CheckResult {
+            status: CheckStatus::Pass,
+            name,
+            detail: format!("{} is writable", root.display()),
+        }
+    } else {
+        CheckResult {
+            status: CheckStatus::Warn,
+            name,
+            detail: format!("{} does not exist yet and will be created", root.display()),
+        }
+    }
+}
+
+/// Checks whether the auto-cd shell function is active in this shell.
+fn check_shell_integration() -> CheckResult {
+    let name = "shell integration";
+    if shell_integration_active() {
+        CheckResult {
+            status: CheckStatus::Pass,
+            name,
+            detail: "autocd function is active".to_string(),
+        }
+    } else {
+        CheckResult {
+            status: CheckStatus::Warn,
+            name,
+            detail: "autocd function not detected; add `source <(gww autocd)` to your shell config"
+                .to_string(),
+        }
+    }
+}
+
+/// Returns true when a file can be created inside the directory.
+fn is_writable_dir(dir: &Path) -> bool {
+    let probe = dir.join(format!(".gww-doctor-{}", std::process::id()));
+    match fs::File::create(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Returns the installed git version.
+fn git_version() -> Result<GitVersion> {
+    let output = git_output(["--version"]).context("Failed to run git --version")?;
+    parse_git_version(&output)
+        .with_context(|| format!("Unrecognized git version: {}", output.trim()))
+}
+
+/// Parses `git --version` output such as `git version 2.39.3 (Apple Git-146)`.
+fn parse_git_version(output: &str) -> Option<GitVersion> {
+    let version = output.trim().strip_prefix("git version ")?;
+    let mut parts = version.split_whitespace().next()?.split('.').map(|part| {
+        part.chars()
+            .take_while(|ch| ch.is_ascii_digit())
+            .collect::<String>()
+            .parse::<u32>()
+            .ok()
+    });
+    let major = parts.next()??;
+    let minor = parts.next().flatten().unwrap_or(0);
+    let patch = parts.next().flatten().unwrap_or(0);
+    Some(GitVersion(major, minor, patch))
+}
+
+/// Prints or opens the compare URL for the current branch against the default branch.
+fn compare_url(open: bool) -> Result<()> {
+    ensure_git_repo()?;
+    let branch = current_branch()?.context("HEAD is detached; check out a branch first")?;
+    let url = git_output(["remote", "get-url", "origin"]).context("No 'origin' remote")?;
+    let remote = parse_remote_url(url.trim())
+        .with_context(|| format!("Unsupported remote URL: {}", url.trim()))?;
+    let base = default_branch();
+    let link = forge_compare_url(&remote, &base, &branch);
+
+    if open {
+        open_url(&link)
+    } else {
+        println!("{link}");
+        Ok(())
+    }
+}
+
+/// Builds a GitLab merge request or GitHub-style compare URL.
+fn forge_compare_url(remote: &RemoteRepo, base: &str, branch: &str) -> String {
+    let repo_url = format!("https://{}/{}/{}", remote.host, remote.owner, remote.name);
+    if remote.host.contains("gitlab") {
+        format!(
+            "{repo_url}/-/merge_requests/new?merge_request[source_branch]={}&merge_request[target_branch]={}",
+            encode_url_component(branch),
+            encode_url_component(base)
+        )
+    } else {
+        format!(
+            "{repo_url}/compare/{}...{}?expand=1",
+            encode_url_component(base),
+            encode_url_component(branch)
+        )
+    }
+}
+
+/// Percent-encodes characters that are not safe in a URL path or query value.
+fn encode_url_component(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Opens a URL with the platform's default handler.
+fn open_url(url: &str) -> Result<()> {
+    let mut cmd = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", "start", ""]);
+        cmd
+    } else {
+        Command::new("xdg-open")
+    };
+    let status = cmd
+        .arg(url)
+        .status()
+        .context("Failed to launch the URL opener")?;
+    if !status.success() {
+        anyhow::bail!("Failed to open {url}");
+    }
+    Ok(())
+}
+
+/// Detects the default branch from `origin/HEAD`, falling back to main or master.
+fn default_branch() -> String {
+    if let Ok(output) = git_output(["symbolic-ref", "--short", "refs/remotes/origin/HEAD"]) {
+        let name = strip_remote_prefix(output.trim());
+        if !name.is_empty() {
+            return name;
+        }
+    }
+    if !branch_exists("main") && branch_exists("master") {
+        "master".to_string()
+    } else {
+        "main".to_string()
+    }
+}
+
+/// Ensures the current (or `--repo`) directory is inside a git repository.
+fn ensure_git_repo() -> Result<()> {
+    git_output(["rev-parse", "--show-toplevel"]).with_context(|| match REPO_DIR.get() {
+        Some(dir) => format!("Not a git repository: {}", dir.display()),
+        None => "Not a git repository".to_string(),
+    })?;
+    Ok(())
+}
+
+/// Records the `--repo` directory that every git invocation runs against.
+fn configure_repo(repo: Option<&Path>) -> Result<()> {
+    let Some(repo) = repo else {
+        return Ok(());
+    };
+    let dir = std::path::absolute(repo)
+        .with_context(|| format!("Failed to resolve {}", repo.display()))?;
+    if !dir.is_dir() {
+        anyhow::bail!("--repo path {} is not a directory", dir.display());
+    }
+    let _ = REPO_DIR.set(dir);
+    Ok(())
+}
+
+/// Returns the directory git commands run in: `--repo` or the current directory.
+fn working_dir() -> Result<PathBuf> {
+    match REPO_DIR.get() {
+        Some(dir) => Ok(dir.clone()),
+        None => env::current_dir().context("Failed to resolve current directory"),
+    }
+}
+
+/// Starts a git command, pointed at the `--repo` directory when one was given.
+///
+/// `GWW_GIT_BIN` replaces `git` for installs outside `PATH`.
+fn git_command() -> Command {
+    let mut cmd = Command::new(git_bin());
+    if let Some(dir) = REPO_DIR.get() {
+        cmd.arg("-C").arg(dir);
+    }
+    cmd
+}
+
+/// Formats a command as a line that can be pasted into a POSIX shell.
+fn shell_command_line(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|word| shell_quote(&word.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Single-quotes a word for the shell unless it only has safe characters.
+fn shell_quote(word: &str) -> String {
+    let safe = |ch: char| ch.is_ascii_alphanumeric() || "-_./:@%+=,".contains(ch);
+    if !word.is_empty() && word.chars().all(safe) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// Returns the git executable to run.
+fn git_bin() -> PathBuf {
+    env::var_os("GWW_GIT_BIN")
+        .filter(|bin| !bin.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("git"))
+}
+
+/// Explains a failure to start git, calling out a missing executable.
+fn git_spawn_error(err: std::io::Error) -> anyhow::Error {
+    if err.kind() == std::io::ErrorKind::NotFound {
+        anyhow::anyhow!(
+            "git executable not found ({}); ensure git is installed and on PATH, or set GWW_GIT_BIN",
+            git_bin().display()
+        )
+    } else {
+        err.into()
+    }
+}
+
+/// Runs a git command and returns stdout on success.
+fn git_output<I, S>(args: I) -> Result<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let output = git_command().args(args).output().map_err(git_spawn_error)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!(stderr.trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Runs `git worktree repair` and reports each record it fixed.
+///
+/// Worktrees whose directory is still missing afterwards are listed with how
+/// to recover them; on git older than 2.30 only that guidance is printed.
+fn repair_worktrees(paths: &[PathBuf]) -> Result<()> {
+    ensure_git_repo()?;
+    if git_version()? < WORKTREE_REPAIR_GIT_VERSION {
+        report_missing_worktrees(&list_worktrees_info()?);
+        let GitVersion(major, minor, patch) = WORKTREE_REPAIR_GIT_VERSION;
+        anyhow::bail!(
+            "gww repair requires git {major}.{minor}.{patch} or newer; move the directories back and use `git worktree move`"
+        );
+    }
+    // git runs with `-C` under `--repo`, so relative paths are resolved here first.
+    let paths = paths
+        .iter()
+        .map(|path| {
+            std::path::absolute(path)
+                .with_context(|| format!("Failed to resolve {}", path.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let output = git_command()
+        .args(["worktree", "repair"])
+        .args(&paths)
+        .output()
+        .map_err(git_spawn_error)
+        .context("Failed to run git worktree repair")?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        anyhow::bail!(stderr.trim().to_string());
+    }
+    let repairs = parse_repair_output(&stderr);
+    for (reason, file) in &repairs {
+        eprintln!(
+            "Repaired {} ({reason})",
+            repaired_worktree(Path::new(file)).display()
+        );
+    }
+    let missing = report_missing_worktrees(&list_worktrees_info()?);
+    if repairs.is_empty() && missing == 0 {
+        eprintln!("All worktrees are intact");
+    }
+    Ok(())
+}
+
+/// Maps a file named by `git worktree repair` to the worktree it belongs to.
+///
+/// An admin `gitdir` file holds the worktree's `.git` path; a `.git` file sits
+/// in the worktree itself.
+fn repaired_worktree(file: &Path) -> PathBuf {
+    let dot_git = match file.file_name().and_then(OsStr::to_str) {
+        Some("gitdir") => fs::read_to_string(file)
+            .map(|content| PathBuf::from(content.trim()))
+            .unwrap_or_else(|_| file.to_path_buf()),
+        _ => file.to_path_buf(),
+    };
+    match dot_git.file_name().and_then(OsStr::to_str) {
+        Some(".git") => dot_git.parent().map_or(dot_git.clone(), Path::to_path_buf),
+        _ => dot_git,
+    }
+}
+
+/// Warns about worktrees whose directory no longer exists and returns how many there are.
+fn report_missing_worktrees(worktrees: &[WorktreeInfo]) -> usize {
+    let missing: Vec<&WorktreeInfo> = worktrees
+        .iter()
+        .filter(|worktree| !worktree.is_main && !worktree.path.exists())
+        .collect();
+    for worktree in &missing {
+        warn(format!(
+            "{} is missing; if it was moved, run `gww repair <new path>`, otherwise `git worktree prune` forgets it",
+            worktree.path.display()
+        ));
+    }
+    missing.len()
+}
+
+/// Reads `repair: <reason>: <file>` lines printed by `git worktree repair`.
+fn parse_repair_output(output: &str) -> Vec<(String, String)> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_prefix("repair: "))
+        .filter_map(|rest| rest.split_once(": "))
+        .map(|(reason, file)| (reason.to_string(), file.to_string()))
+        .collect()
+}
+
+/// Loads detailed worktree entries from git.
+fn list_worktrees_info() -> Result<Vec<WorktreeInfo>> {
+    let output = git_output(["worktree", "list", "--porcelain"])?;
+    Ok(parse_worktree_porcelain(&output))
+}
+
+/// Parses `git worktree list --porcelain` output into worktree entries.
+fn parse_worktree_porcelain(output: &str) -> Vec<WorktreeInfo> {
+    let mut worktrees = Vec::new();
+    let mut current: Option<WorktreeInfo> = None;
+
+    for line in output.lines() {
+        if let Some(path) = line.strip_prefix("worktree ") {
+            if let Some(worktree) = current.take() {
+                worktrees.push(worktree);
+            }
+            current = Some(WorktreeInfo {
+                path: PathBuf::from(path),
+                is_main: worktrees.is_empty(),
+                ..WorktreeInfo::default()
+            });
+        } else if let Some(worktree) = current.as_mut() {
+            // Attributes git adds in later versions are skipped.
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let value = value.trim();
+            match key {
+                "branch" => {
+                    worktree.branch = value.strip_prefix("refs/heads/").map(|b| b.to_string());
+                }
+                "HEAD" => worktree.head = Some(value.to_string()),
+                "detached" => worktree.detached = true,
+                "bare" => worktree.bare = true,
+                "locked" => worktree.locked = Some(value.to_string()),
+                "prunable" => worktree.prunable = Some(value.to_string()),
+                _ => {}
+            }
+        }
+    }
+    if let Some(worktree) = current {
+        worktrees.push(worktree);
+    }
+
+    worktrees
+}
+
+/// Returns true when the worktree has a commit checked out without a branch.
+fn is_detached(worktree: &WorktreeInfo) -> bool {
+    worktree.detached && worktree.head.is_some()
+}
+
+/// Describes a worktree by branch, or by short SHA and path when detached.
+fn worktree_label(worktree: &WorktreeInfo) -> String {
+    match (&worktree.branch, &worktree.head) {
+        (Some(branch), _) => branch.clone(),
+        (None, Some(head)) => format!("(detached {}) {}", short_sha(head), worktree.path.display()),
+        (None, None) => worktree.path.display().to_string(),
+    }
+}
+
+/// Abbreviates a commit SHA for display.
+fn short_sha(sha: &str) -> &str {
+    sha.get(..7).unwrap_or(sha)
+}
+
+/// Orders unique branch names by most recent commit time.
+///
+/// Equal timestamps fall back to `tie_break`; the sort is stable, so
+/// `TieBreak::Insertion` keeps the input order. Names without metadata always
+/// sort last, alphabetically among themselves.
+fn sort_by_recent<I>(
+    names: I,
+    meta: &HashMap<String, BranchMeta>,
+    tie_break: TieBreak,
+) -> Vec<String>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut unique: Vec<String> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    for name in names {
+        let name = name.as_ref().to_string();
+        if seen.insert(name.clone()) {
+            unique.push(name);
+        }
+    }
+
+    unique.sort_by(|a, b| match (meta.get(a), meta.get(b)) {
+        (Some(a_meta), Some(b_meta)) => b_meta
+            .timestamp_unix
+            .cmp(&a_meta.timestamp_unix)
+            .then_with(|| match tie_break {
+                TieBreak::NameAsc => a.cmp(b),
+                TieBreak::NameDesc => b.cmp(a),
+                TieBreak::Insertion => std::cmp::Ordering::Equal,
+            }),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => a.cmp(b),
+    });
+    unique
+}
+
+/// Collects commit metadata for local and remote branches.
+///
+/// Upstream tracking is only requested when `track_info` is set, since
+/// computing ahead/behind counts walks history for every branch.
+fn batch_branch_metadata(track_info: bool) -> Result<HashMap<String, BranchMeta>> {
+    query_branch_metadata(&["refs/heads", "refs/remotes"], track_info)
+}
+
+/// Collects commit metadata for the given full ref names only, skipping the full-repo scan.
+///
+/// git also matches refs below each name (`refs/heads/a` covers `refs/heads/a/b`),
+/// so the map may hold a few extra entries.
+fn branch_metadata_for<S: AsRef<str>>(
+    refs: &[S],
+    track_info: bool,
+) -> Result<HashMap<String, BranchMeta>> {
+    if refs.is_empty() {
+        return Ok(HashMap::new());
+    }
+    query_branch_metadata(refs, track_info)
+}
+
+/// Collects commit metadata for just the branches checked out in worktrees.
+fn worktree_branch_metadata(
+    worktrees: &[WorktreeInfo],
+    track_info: bool,
+) -> Result<HashMap<String, BranchMeta>> {
+    branch_metadata_for(&worktree_refs(worktrees), track_info)
+}
+
+/// Returns the full ref names of branches checked out in worktrees.
+fn worktree_refs(worktrees: &[WorktreeInfo]) -> Vec<String> {
+    worktrees
+        .iter()
+        .filter_map(|wt| wt.branch.as_deref())
+        .map(|branch| format!("refs/heads/{branch}"))
+        .collect()
+}
+
+/// Runs `for-each-ref` over `patterns` and parses the metadata lines.
+fn query_branch_metadata<S: AsRef<str>>(
+    patterns: &[S],
+    track_info: bool,
+) -> Result<HashMap<String, BranchMeta>> {
+    let track = if track_info {
+        "%(upstream)\t%(upstream:track)"
+    } else {
+        "\t"
+    };
+    let format = format!(
+        "--format=%(refname:short)\t%(committerdate:unix)\t%(committerdate:iso8601-strict)\t%(authorname)\t{track}\t%(subject)"
+    );
+    let output = git_output(
+        ["for-each-ref", format.as_str()]
+            .into_iter()
+            .chain(patterns.iter().map(AsRef::as_ref)),
+    )?;
+    Ok(parse_branch_metadata(&output))
+}
+
+/// Parses `for-each-ref` metadata lines into a map keyed by short ref name.
+fn parse_branch_metadata(output: &str) -> HashMap<String, BranchMeta> {
+    let mut map = HashMap::new();
+    for line in output.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut parts = line.splitn(7, '\t');
+        let refname = parts.next().unwrap_or("").trim().to_string();
+        if refname.is_empty() {
+            continue;
+        }
+        let timestamp_unix = parts
+            .next()
+            .and_then(|value| value.trim().parse::<i64>().ok())
+            .unwrap_or(0);
+        let timestamp_label = parts.next().unwrap_or("").trim().to_string();
+        let author = parts.next().unwrap_or("").trim().to_string();
+        let upstream = parts.next().unwrap_or("");
+        let track = parse_upstream_track(upstream, parts.next().unwrap_or(""));
+        let subject = parts.next().unwrap_or("").trim().to_string();
+        map.insert(
+            refname,
+            BranchMeta {
+                timestamp_unix,
+                summary: BranchSummary {
+                    timestamp_label,
+                    author,
+                    subject,
+                    track,
+                },
+            },
+        );
+    }
+    map
+}
+
+/// Parses `%(upstream:track)` output such as `[ahead 2, behind 1]` or `[gone]`.
+///
+/// Git leaves the track empty when a branch matches its upstream, so an empty
+/// track with a set `upstream` is in sync. Returns `None` for branches without
+/// an upstream or with unexpected output.
+fn parse_upstream_track(upstream: &str, value: &str) -> Option<UpstreamTrack> {
+    if upstream.trim().is_empty() {
+        return None;
+    }
+    let value = value.trim();
+    if value.is_empty() {
+        return Some(UpstreamTrack::Counts {
+            ahead: 0,
+            behind: 0,
+        });
+    }
+    let inner = value.strip_prefix('[')?.strip_suffix(']')?;
+    if inner == "gone" {
+        return Some(UpstreamTrack::Gone);
+    }
+    let mut ahead = 0;
+    let mut behind = 0;
+    for part in inner.split(',') {
+        match part.trim().split_once(' ') {
+            Some(("ahead", count)) => ahead = count.parse().ok()?,
+            Some(("behind", count)) => behind = count.parse().ok()?,
+            _ => return None,
+        }
+    }
+    Some(UpstreamTrack::Counts { ahead, behind })
+}
+
+/// Formats upstream tracking as `↑2 ↓1`, or `None` when in sync or untracked.
+fn format_upstream_track(track: Option<UpstreamTrack>) -> Option<String> {
+    match track? {
+        UpstreamTrack::Gone => Some("(upstream gone)".to_string()),
+        UpstreamTrack::Counts { ahead, behind } => {
+            let mut counts = Vec::new();
+            if ahead > 0 {
+                counts.push(format!("↑{ahead}"));
+            }
+            if behind > 0 {
+                counts.push(format!("↓{behind}"));
+            }
+            (!counts.is_empty()).then(|| counts.join(" "))
+        }
+    }
+}
+
+/// Builds a fallback branch summary when metadata is missing.
+fn placeholder_summary() -> BranchSummary {
+    BranchSummary {
+        timestamp_label: "unknown time".to_string(),
+        author: "unknown author".to_string(),
+        subject: "unknown subject".to_string(),
+        track: None,
+    }
+}
+
+/// Formats a branch entry for display in the selector.
+///
+/// With `age_color`, the timestamp is colored by how recent the last commit is
+/// and branches older than the last `age_color_days` threshold are dimmed.
+/// With `no_summary`, only the tag and branch name are shown.
+fn format_branch_item(info: &BranchInfo, options: &CandidateOptions) -> String {
+    let config = config::get();
+    let labels = &config.labels;
+    let (label, tag_style) = match info.source {
+        BranchSource::Worktree => (labels.worktree(), config.theme.worktree_tag()),
+        BranchSource::Local => (labels.local(), config.theme.local_tag()),
+        BranchSource::Remote => (labels.remote(), config.theme.remote_tag()),
+    };
+    let label_width = [labels.worktree(), labels.local(), labels.remote()]
+        .iter()
+        .map(|label| label.chars().count())
+        .max()
+        .unwrap_or(1);
+    let marker = if info.is_current { "*" } else { " " };
+    let tag = format!("[{label}{marker}]");
+    let padding = tag_padding(&tag, label_width);
+
+    if options.no_summary {
+        return if is_color_enabled() {
+            let name = config.theme.branch().apply_to(&info.name);
+            format!("{}{padding} {}", tag_style.apply_to(tag), name)
+        } else {
+            format!("{tag}{padding} {}", info.name)
+        };
+    }
+
+    let subject = format!("\"{}\"", info.summary.subject);
+    let author = format!("[{}]", info.summary.author);
+    let timestamp = format!("({})", info.summary.timestamp_label);
+    let track = format_upstream_track(info.summary.track);
+
+    if is_color_enabled() {
+        let theme = &config.theme;
+        let bucket = info
+            .timestamp_unix
+            .filter(|_| options.age_color)
+            .map(|timestamp| age_bucket(unix_now() - timestamp, config.age_color_days()));
+        let stale = bucket == Some(AgeBucket::Older);
+        let dim_if_stale = |style: Style| if stale { style.dim() } else { style };
+        let timestamp_style = match bucket {
+            Some(AgeBucket::Today) => Style::new().green().bold(),
+            Some(AgeBucket::Week) => Style::new().green(),
+            Some(AgeBucket::Month) => Style::new().yellow(),
+            Some(AgeBucket::Older) => Style::new().dim(),
+            None => theme.timestamp(),
+        };
+        let tag = dim_if_stale(tag_style).apply_to(tag);
+        let name = dim_if_stale(theme.branch()).apply_to(&info.name);
+        let subject = dim_if_stale(theme.subject()).apply_to(subject);
+        let author = dim_if_stale(theme.author()).apply_to(author);
+        let timestamp = timestamp_style.apply_to(timestamp);
+        let line = format!(
+            "{}{padding} {} {} {} {}",
+            tag, name, subject, author, timestamp
+        );
+        match track {
+            Some(track) => format!("{} {}", line, style(track).green()),
+            None => line,
+        }
+    } else {
+        let line = format!(
+            "{tag}{padding} {} {} {} {}",
+            info.name, subject, author, timestamp
+        );
+        match track {
+            Some(track) => format!("{line} {track}"),
+            None => line,
+        }
+    }
+}
+
+/// Buckets a commit age in seconds by the `today`, `week`, and `month` day thresholds.
+fn age_bucket(age_secs: i64, days: [u32; 3]) -> AgeBucket {
+    let [today, week, month] = days.map(|days| i64::from(days) * 24 * 60 * 60);
+    if age_secs < today {
+        AgeBucket::Today
+    } else if age_secs < week {
+        AgeBucket::Week
+    } else if age_secs < month {
+        AgeBucket::Month
+    } else {
+        AgeBucket::Older
+    }
+}
+
+/// Returns the spaces that line a `[label*]` tag up with the widest configured label.
+///
+/// Kept outside the tag so background styles do not extend over the padding.
+fn tag_padding(tag: &str, label_width: usize) -> String {
+    " ".repeat((label_width + 3).saturating_sub(tag.chars().count()))
+}
+
+/// Returns true when ANSI color output is enabled.
+fn is_color_enabled() -> bool {
+    env::var("GWW_NO_COLOUR").is_err()
+}
+
+/// Applies the configured color settings to console output.
+fn configure_colors() {
+    if is_color_enabled() {
+        console::set_colors_enabled(true);
+    } else {
+        console::set_colors_enabled(false);
+    }
+}
+
+/// Records whether confirmation prompts should be accepted automatically.
+fn configure_prompts(assume_yes: bool) {
+    ASSUME_YES.store(assume_yes, Ordering::Relaxed);
+}
+
+/// Prints a diagnostic message to stderr when `--verbose` is set.
+fn verbose(message: String) {
+    if VERBOSE.load(Ordering::Relaxed) {
+        eprintln!("{}", style(message).dim());
+    }
+}
+
+/// Reports a recoverable problem without failing the command.
+///
+/// Under the shell function it goes to stdout as a `GWW_ERR:` line, which the
+/// function moves to stderr; otherwise it is printed to stderr directly.
+fn warn(message: String) {
+    if shell_integration_active() {
+        println!("{ERR_PREFIX}{message}");
+    } else {
+        eprintln!("Warning: {message}");
+    }
+}
+
+/// Returns false when choices must be made without asking (`--yes`, batch mode, no terminal).
+fn prompts_enabled() -> bool {
+    !ASSUME_YES.load(Ordering::Relaxed)
+        && !NON_INTERACTIVE.load(Ordering::Relaxed)
+        && console::Term::stderr().is_term()
+}
+
+/// Asks a yes/no question, accepting it without interaction under `--yes`.
+///
+/// In non-interactive batch mode the default answer is taken instead.
+fn confirm(prompt: String, default: bool) -> Result<bool> {
+    if ASSUME_YES.load(Ordering::Relaxed) {
+        return Ok(true);
+    }
+    if NON_INTERACTIVE.load(Ordering::Relaxed) {
+        verbose(format!("{prompt} [{}]", if default { "yes" } else { "no" }));
+        return Ok(default);
+    }
+    Ok(Confirm::new()
+        .with_prompt(prompt)
+        .default(default)
+        .interact()?)
+}
+
+/// Lists local branch names.
+fn list_local_branches() -> Result<Vec<String>> {
+    let output = git_output(["for-each-ref", "refs/heads", "--format=%(refname:short)"])?;
+    Ok(output
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect())
+}
+
+/// Lists remote branch names, excluding HEAD entries.
+fn list_remote_branches() -> Result<Vec<String>> {
+    let output = git_output(["for-each-ref", "refs/remotes", "--format=%(refname:short)"])?;
+    let branches = output
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .filter(|s| s.contains('/'))
+        .filter(|s| !s.ends_with("/HEAD"))
+        .collect();
+    Ok(branches)
+}
+
+/// Returns the currently checked-out branch name, if any.
+///
+/// An unborn branch in a repository without commits is still reported.
+fn current_branch() -> Result<Option<String>> {
+    let output = git_command()
+        .args(["symbolic-ref", "--quiet", "--short", "HEAD"])
+        .output()
+        .map_err(git_spawn_error)
+        .context("Failed to run git symbolic-ref")?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let name = stdout.lines().next().unwrap_or("").trim();
+    if name.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(name.to_string()))
+    }
+}
+
+/// Prompts for a branch name across worktrees, local, and remote, limited to the options' sources.
+fn select_branch(
+    worktrees: &[WorktreeInfo],
+    locals: &[String],
+    remotes: &[String],
+    options: &CandidateOptions,
+) -> Result<BranchSelection> {
+    let candidates = {
+        let _spinner = spinner::Spinner::start("Loading branches...");
+        build_branch_candidates(worktrees, locals, remotes, options)?
+    };
+
+    if candidates.is_empty() {
+        if !ref_resolves("HEAD") {
+            anyhow::bail!(
+                "No branches found: this repository has no commits yet. Create the first worktree with `gww checkout -b <branch>`"
+            );
+        }
+        anyhow::bail!("No branches found");
+    }
+
+    let sources: Vec<BranchSource> = candidates
+        .iter()
+        .map(|candidate| candidate.source)
+        .collect();
+    let entries = selector_entries(&sources, options.sections);
+    let items: Vec<String> = entries
+        .iter()
+        .map(|entry| match *entry {
+            SelectorEntry::Header(source) => format_section_header(source),
+            SelectorEntry::Branch(index) => format_branch_item(&candidates[index], options),
+            SelectorEntry::Create => format_create_item(),
+        })
+        .collect();
+
+    // Dividers are ordinary items to dialoguer, so they take part in fuzzy
+    // matching and picking one reopens the selector (without the query) on the
+    // first branch below it. That is why sections are opt-in.
+    let mut default = entries
+        .iter()
+        .position(|entry| !matches!(entry, SelectorEntry::Header(_)))
+        .unwrap_or(0);
+    loop {
+        let selection = FuzzySelect::new()
+            .with_prompt("Select branch")
+            .items(&items)
+            .default(default)
+            .interact_opt()?;
+
+        let Some(selection) = selection else {
+            anyhow::bail!("Selection cancelled");
+        };
+
+        match entries[selection] {
+            SelectorEntry::Header(_) => default = selection + 1,
+            SelectorEntry::Branch(index) => {
+                return Ok(BranchSelection::Existing(candidates[index].name.clone()));
+            }
+            SelectorEntry::Create => return prompt_new_branch(),
+        }
+    }
+}
+
+/// Lays out selector rows: candidates in order, a divider before each source
+/// group when `sections` is set and more than one group is present, and the
+/// create entry last.
+fn selector_entries(sources: &[BranchSource], sections: bool) -> Vec<SelectorEntry> {
+    let grouped = sections && sources.windows(2).any(|pair| pair[0] != pair[1]);
+    let mut entries = Vec::with_capacity(sources.len() + 4);
+    let mut previous = None;
+    for (index, &source) in sources.iter().enumerate() {
+        if grouped && previous != Some(source) {
+            entries.push(SelectorEntry::Header(source));
+        }
+        previous = Some(source);
+        entries.push(SelectorEntry::Branch(index));
+    }
+    entries.push(SelectorEntry::Create);
+    entries
+}
+
+/// Formats the divider shown above a source group in the selector.
+fn format_section_header(source: BranchSource) -> String {
+    let title = match source {
+        BranchSource::Worktree => "Worktrees",
+        BranchSource::Local => "Local",
+        BranchSource::Remote => "Remote",
+    };
+    let header = format!("── {title} ──");
+    if is_color_enabled() {
+        style(header).dim().to_string()
+    } else {
+        header
+    }
+}
+
+/// Formats the synthetic selector entry that starts the create flow.
+fn format_create_item() -> String {
+    if is_color_enabled() {
+        style(CREATE_BRANCH_ITEM).green().bold().to_string()
+    } else {
+        CREATE_BRANCH_ITEM.to_string()
+    }
+}
+
+/// Prompts for the name and optional base of a branch to create.
+fn prompt_new_branch() -> Result<BranchSelection> {
+    let name: String = Input::new()
+        .with_prompt("New branch name")
+        .interact_text()?;
+    let base: String = Input::new()
+        .with_prompt("Base ref (empty for HEAD)")
+        .with_initial_text(recorded_base(name.trim()).unwrap_or_default())
+        .allow_empty(true)
+        .interact_text()?;
+    Ok(BranchSelection::Create {
+        name: name.trim().to_string(),
+        base: non_empty_trimmed(&base),
+    })
+}
+
+/// Returns the trimmed value, or `None` when it is blank.
+fn non_empty_trimmed(value: &str) -> Option<String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed.to_string())
+    }
+}
+
+/// Builds branch candidates with metadata for selection.
+fn build_branch_candidates(
+    worktrees: &[WorktreeInfo],
+    locals: &[String],
+    remotes: &[String],
+    options: &CandidateOptions,
+) -> Result<Vec<BranchInfo>> {
+    // Worktree-only selectors query just their own branches.
+    let meta = if options.no_summary {
+        HashMap::new()
+    } else if options.sources == [BranchSource::Worktree] {
+        worktree_branch_metadata(worktrees, options.track_info)?
+    } else {
+        batch_branch_metadata(options.track_info)?
+    };
+    let current = current_branch()?;
+    Ok(assemble_branch_candidates(
+        worktrees,
+        locals,
+        remotes,
+        options,
+        &meta,
+        current.as_deref(),
+    ))
+}
+
+/// Orders and de-duplicates branch candidates from pre-fetched metadata.
+///
+/// Local and remote branches matching the filter's ignore patterns are
+/// dropped, while worktree branches are always kept. Date bounds apply to all
+/// sources, and only the sources listed in the options are returned.
+///
+/// The current branch leads the worktree group when `current_first` is set; a
+/// pinned default branch is placed ahead of it in its own group.
+fn assemble_branch_candidates(
+    worktrees: &[WorktreeInfo],
+    locals: &[String],
+    remotes: &[String],
+    options: &CandidateOptions,
+    meta: &HashMap<String, BranchMeta>,
+    current_branch: Option<&str>,
+) -> Vec<BranchInfo> {
+    let mut candidates: Vec<BranchInfo> = Vec::new();
+    let worktree_set: HashSet<String> = worktrees
+        .iter()
+        .filter_map(|wt| wt.branch.clone())
+        .collect();
+    let tie_break = config::get().tie_break;
+    let mut worktree_names = sort_by_recent(
+        worktrees.iter().filter_map(|wt| wt.branch.as_deref()),
+        meta,
+        tie_break,
+    );
+    let mut local_names = sort_by_recent(locals, meta, tie_break);
+    let access_times = options.access_times.as_ref();
+    apply_sort(&mut worktree_names, options.sorts.worktree, access_times);
+    apply_sort(&mut local_names, options.sorts.local, access_times);
+    if options.current_first
+        && let Some(current) = current_branch
+    {
+        pin_to_front(&mut worktree_names, current);
+    }
+    if let Some(main) = options.pinned_main.as_deref() {
+        pin_to_front(&mut worktree_names, main);
+        pin_to_front(&mut local_names, main);
+    }
+    let mut remote_names = sort_by_recent(remotes, meta, tie_break);
+    apply_sort(&mut remote_names, options.sorts.remote, access_times);
+
+    for name in worktree_names {
+        if !in_date_range(&name, meta, options) {
+            continue;
+        }
+        let (summary, timestamp_unix) = candidate_summary(meta, &name);
+
+        candidates.push(BranchInfo {
+            is_current: current_branch == Some(name.as_str()),
+            summary,
+            timestamp_unix,
+            name,
+            source: BranchSource::Worktree,
+        });
+    }
+
+    for name in local_names {
+        if !worktree_set.contains(&name)
+            && !is_ignored_branch(&name, &options.ignore)
+            && in_date_range(&name, meta, options)
+        {
+            let (summary, timestamp_unix) = candidate_summary(meta, &name);
+
+            candidates.push(BranchInfo {
+                is_current: current_branch == Some(name.as_str()),
+                summary,
+                timestamp_unix,
+                name,
+                source: BranchSource::Local,
+            });
+        }
+    }
+
+    for name in remote_names {
+        let local_name = strip_remote_prefix(&name);
+        let has_local = locals.iter().any(|local| local == &local_name);
+        let shadowed = worktree_set.contains(&local_name) || has_local;
+        if (options.all_remotes || !shadowed)
+            && !is_ignored_branch(&name, &options.ignore)
+            && !is_ignored_branch(&local_name, &options.ignore)
+            && in_date_range(&name, meta, options)
+        {
+            let (summary, timestamp_unix) = candidate_summary(meta, &name);
+            candidates.push(BranchInfo {
+                is_current: current_branch == Some(local_name.as_str()),
+                summary,
+                timestamp_unix,
+                name,
+                source: BranchSource::Remote,
+            });
+        }
+    }
+
+    filter_by_source(candidates, &options.sources)
+}
+
+/// Looks up a branch's display summary and commit time, with placeholders when missing.
+fn candidate_summary(
+    meta: &HashMap<String, BranchMeta>,
+    name: &str,
+) -> (BranchSummary, Option<i64>) {
+    match meta.get(name) {
+        Some(info) => (info.summary.clone(), Some(info.timestamp_unix)),
+        None => (placeholder_summary(), None),
+    }
+}
+
+/// Reorders names already sorted by commit time according to a group's sort key.
+fn apply_sort(names: &mut [String], key: BranchSort, access_times: Option<&HashMap<String, i64>>) {
+    match key {
+        BranchSort::Commit => {}
+        BranchSort::Accessed => {
+            if let Some(access_times) = access_times {
+                sort_by_access(names, access_times);
+            }
+        }
+        BranchSort::Name => names.sort(),
+    }
+}
+
+/// Parses a `--sort` value: `KEY` for every group or `SOURCE=KEY` for one.
+fn parse_sort_spec(value: &str) -> Result<SortSpec, String> {
+    let (source, key) = match value.split_once('=') {
+        Some((source, key)) => (Some(BranchSource::from_str(source, true)?), key),
+        None => (None, value),
+    };
+    Ok(SortSpec {
+        source,
+        key: BranchSort::from_str(key, true)?,
+    })
+}
+
+/// Resolves each group's sort key from the config, then `--sort` values in order.
+fn resolve_source_sorts(specs: &[SortSpec], configured: &config::SortOrder) -> SourceSorts {
+    let from_config = |key: Option<config::SortKey>| match key {
+        Some(config::SortKey::Commit) | None => BranchSort::Commit,
+        Some(config::SortKey::Accessed) => BranchSort::Accessed,
+        Some(config::SortKey::Name) => BranchSort::Name,
+    };
+    let mut sorts = SourceSorts {
+        worktree: from_config(configured.worktree),
+        local: from_config(configured.local),
+        remote: from_config(configured.remote),
+    };
+    for spec in specs {
+        if matches!(spec.source, None | Some(BranchSource::Worktree)) {
+            sorts.worktree = spec.key;
+        }
+        if matches!(spec.source, None | Some(BranchSource::Local)) {
+            sorts.local = spec.key;
+        }
+        if matches!(spec.source, None | Some(BranchSource::Remote)) {
+            sorts.remote = spec.key;
+        }
+    }
+    sorts
+}
+
+/// Moves visited branches ahead, newest visit first, keeping the existing order otherwise.
+fn sort_by_access(names: &mut [String], access_times: &HashMap<String, i64>) {
+    names.sort_by_key(|name| std::cmp::Reverse(access_times.get(name).copied()));
+}
+
+/// Moves `name` to the front of `names` when present.
+fn pin_to_front(names: &mut Vec<String>, name: &str) {
+    if let Some(pos) = names.iter().position(|candidate| candidate == name) {
+        let pinned = names.remove(pos);
+        names.insert(0, pinned);
+    }
+}
+
+/// Resolves an on/off flag pair, falling back to the config value and then `default`.
+fn resolve_toggle(on: bool, off: bool, configured: Option<bool>, default: bool) -> bool {
+    if on {
+        true
+    } else if off {
+        false
+    } else {
+        configured.unwrap_or(default)
+    }
+}
+
+/// Builds the selector filter from checkout flags and configured ignore patterns.
+fn candidate_options(args: &CheckoutArgs) -> Result<CandidateOptions> {
+    let config = config::get();
+    let main_first = resolve_toggle(
+        args.main_first,
+        args.no_main_first,
+        config.main_first,
+        false,
+    );
+    let sorts = resolve_source_sorts(&args.sort, &config.sort);
+    Ok(CandidateOptions {
+        ignore: if args.no_ignore {
+            Vec::new()
+        } else {
+            ignore_patterns()
+        },
+        since_unix: args.since.as_deref().map(parse_git_date).transpose()?,
+        until_unix: args.until.as_deref().map(parse_git_date).transpose()?,
+        track_info: args.track_info,
+        age_color: args.age_color || config.age_color,
+        no_summary: args.no_summary,
+        sections: resolve_toggle(args.sections, args.no_sections, config.sections, false),
+        current_first: resolve_toggle(
+            args.current_first,
+            args.no_current_first,
+            config.current_first,
+            true,
+        ),
+        pinned_main: main_first.then(default_branch),
+        all_remotes: args.all_remotes,
+        sorts,
+        access_times: if [sorts.worktree, sorts.local, sorts.remote].contains(&BranchSort::Accessed)
+        {
+            Some(state::access_times(&state::load_visits()?))
+        } else {
+            None
+        },
+        sources: if args.worktrees_only {
+            vec![BranchSource::Worktree]
+        } else {
+            Vec::new()
+        },
+    })
+}
+
+/// Resolves a relative or absolute date to a Unix timestamp using git's date parser.
+fn parse_git_date(date: &str) -> Result<i64> {
+    let since = format!("--since={date}");
+    let output = git_output(["rev-parse", since.as_str()])
+        .with_context(|| format!("Failed to parse date '{date}'"))?;
+    output
+        .trim()
+        .strip_prefix("--max-age=")
+        .and_then(|value| value.parse::<i64>().ok())
+        .with_context(|| format!("Failed to parse date '{date}'"))
+}
+
+/// Returns true when the branch's commit time falls within the filter's dates.
+///
+/// Branches without metadata count as timestamp 0, so a `since` bound drops them.
+fn in_date_range(
+    name: &str,
+    meta: &HashMap<String, BranchMeta>,
+    options: &CandidateOptions,
+) -> bool {
+    let timestamp = meta.get(name).map(|info| info.timestamp_unix).unwrap_or(0);
+    options.since_unix.is_none_or(|since| timestamp >= since)
+        && options.until_unix.is_none_or(|until| timestamp <= until)
+}
+
+/// Reads ignore patterns from comma-separated `GWW_IGNORE`, falling back to config.
+fn ignore_patterns() -> Vec<String> {
+    match env::var("GWW_IGNORE") {
+        Ok(value) => value
+            .split(',')
+            .map(|pattern| pattern.trim().to_string())
+            .filter(|pattern| !pattern.is_empty())
+            .collect(),
+        Err(_) => config::get().ignore_branches.clone(),
+    }
+}
+
+/// Returns true when the branch name matches any ignore pattern.
+fn is_ignored_branch(name: &str, ignore: &[String]) -> bool {
+    ignore.iter().any(|pattern| glob_match(pattern, name))
+}
+
+/// Matches `text` against a glob where `*` spans any characters and `?` one.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|ch| *ch == '*')
+}
+
+/// Prompts for a worktree among branch and detached worktrees.
+fn select_worktree(worktrees: &[WorktreeInfo], include_main: bool) -> Result<WorktreeInfo> {
+    let choices = removable_worktrees(worktrees, include_main);
+    if choices.is_empty() {
+        if worktrees.iter().any(|wt| wt.is_main) && !include_main {
+            anyhow::bail!("Nothing to remove: only the main worktree exists");
+        }
+        anyhow::bail!("No worktrees found");
+    }
+
+    let items: Vec<String> = choices.iter().map(|wt| worktree_label(wt)).collect();
+    let selection = FuzzySelect::new()
+        .with_prompt("Select worktree")
+        .items(&items)
+        .default(0)
+        .interact_opt()?;
+
+    let Some(selection) = selection else {
+        anyhow::bail!("Selection cancelled");
+    };
+
+    Ok(choices[selection].clone())
+}
+
+/// Lists selector entries for removal: branches first, then detached worktrees.
+///
+/// The main worktree cannot be removed by git, so it is left out unless asked for.
+fn removable_worktrees(worktrees: &[WorktreeInfo], include_main: bool) -> Vec<&WorktreeInfo> {
+    let mut choices: Vec<&WorktreeInfo> = worktrees
+        .iter()
+        .filter(|wt| include_main || !wt.is_main)
+        .filter(|wt| wt.branch.is_some() || is_detached(wt))
+        .collect();
+    choices.sort_by(|a, b| {
+        is_detached(a)
+            .cmp(&is_detached(b))
+            .then_with(|| a.branch.cmp(&b.branch))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    choices
+}
+
+/// Finds the worktree entry that matches a branch.
+///
+/// Git only allows a branch in two worktrees when forced (`worktree add -f`);
+/// the first entry in `git worktree list` order is returned then, which puts
+/// the main worktree first and the rest in creation order.
+fn worktree_for_branch<'a>(
+    worktrees: &'a [WorktreeInfo],
+    branch: &str,
+) -> Option<&'a WorktreeInfo> {
+    worktrees
+        .iter()
+        .find(|wt| wt.branch.as_deref() == Some(branch))
+}
+
+/// Finds the worktree for a branch, asking which one to use when several have it checked out.
+///
+/// Candidates are offered most recently visited first; under `--yes` that
+/// entry is taken without asking.
+fn pick_worktree_for_branch<'a>(
+    worktrees: &'a [WorktreeInfo],
+    branch: &str,
+) -> Result<Option<&'a WorktreeInfo>> {
+    let mut matches: Vec<&WorktreeInfo> = worktrees
+        .iter()
+        .filter(|wt| wt.branch.as_deref() == Some(branch))
+        .collect();
+    if matches.len() <= 1 {
+        return Ok(matches.first().copied());
+    }
+    order_by_last_visit(&mut matches, &state::load_visits().unwrap_or_default());
+    if ASSUME_YES.load(Ordering::Relaxed) || NON_INTERACTIVE.load(Ordering::Relaxed) {
+        return Ok(matches.first().copied());
+    }
+    let items: Vec<String> = matches.iter().map(|wt| worktree_label(wt)).collect();
+    let selection = FuzzySelect::new()
+        .with_prompt(format!(
+            "Branch '{branch}' is checked out in several worktrees"
+        ))
+        .items(&items)
+        .default(0)
+        .interact_opt()?;
+    let Some(selection) = selection else {
+        anyhow::bail!("Selection cancelled");
+    };
+    Ok(Some(matches[selection]))
+}
+
+/// Orders worktrees by their latest visit, newest first, keeping git's order for unvisited ones.
+fn order_by_last_visit(worktrees: &mut [&WorktreeInfo], visits: &[state::Visit]) {
+    worktrees.sort_by_cached_key(|wt| {
+        let resolved = canonical_path(&wt.path);
+        std::cmp::Reverse(
+            visits
+                .iter()
+                .filter(|visit| visit.path == wt.path || visit.path == resolved)
+                .map(|visit| visit.visited_unix)
+                .max(),
+        )
+    });
+}
+
+/// Finds a remote branch that matches the provided name.
+///
+/// An exact `remote/branch` name wins over a match on the name without its
+/// remote prefix. `checkout` only consults this after local branches, unless
+/// `--prefer-remote` is given.
+fn match_remote_branch(branch: &str, remotes: &[String]) -> Option<String> {
+    remote_branch_matches(branch, remotes).into_iter().next()
+}
+
+/// Lists every remote branch `branch` could mean, `origin` first.
+///
+/// A full remote ref such as `upstream/dev` only matches itself.
+fn remote_branch_matches(branch: &str, remotes: &[String]) -> Vec<String> {
+    if remotes.iter().any(|b| b == branch) {
+        return vec![branch.to_string()];
+    }
+    let mut matches: Vec<String> = remotes
+        .iter()
+        .filter(|remote| strip_remote_prefix(remote) == branch)
+        .cloned()
+        .collect();
+    matches.sort_by_key(|remote| !remote.starts_with("origin/"));
+    matches
+}
+
+/// Picks the remote branch to check out when several remotes have `branch`.
+///
+/// `--remote` decides without asking; otherwise interactive runs choose from a
+/// list and non-interactive runs take the first match with a warning.
+fn pick_remote_branch(
+    args: &CheckoutArgs,
+    branch: &str,
+    remotes: &[String],
+) -> Result<Option<String>> {
+    let matches = remote_branch_matches(branch, remotes);
+    if let Some(remote) = args.remote.as_deref() {
+        if matches.is_empty() {
+            anyhow::bail!(
+                "No remote has branch '{branch}'; fetch it first or drop --remote {remote}"
+            );
+        }
+        let prefix = format!("{remote}/");
+        return match matches.iter().find(|name| name.starts_with(&prefix)) {
+            Some(name) => Ok(Some(name.clone())),
+            None => anyhow::bail!(
+                "Remote '{remote}' has no branch '{branch}'; found {}",
+                matches.join(", ")
+            ),
+        };
+    }
+    if matches.len() < 2 {
+        return Ok(matches.into_iter().next());
+    }
+    if !prompts_enabled() {
+        warn(format!(
+            "'{branch}' exists on several remotes ({}); using {} (pass --remote to choose)",
+            matches.join(", "),
+            matches[0]
+        ));
+        return Ok(matches.into_iter().next());
+    }
+    let selection = Select::new()
+        .with_prompt(format!("'{branch}' exists on several remotes"))
+        .items(&matches)
+        .default(0)
+        .interact_opt()?;
+    match selection {
+        Some(index) => Ok(Some(matches[index].clone())),
+        None => anyhow::bail!("Selection cancelled"),
+    }
+}
+
+/// Strips the remote prefix from a branch name.
+fn strip_remote_prefix(branch: &str) -> String {
+    branch
+        .split_once('/')
+        .map(|(_, rest)| rest.to_string())
+        .unwrap_or_else(|| branch.to_string())
+}
+
+/// Ensures a branch exists or prompts for creation from an optional base.
+fn ensure_branch_or_prompt(
+    branch: &str,
+    create: bool,
+    remote: Option<&str>,
+    base: Option<&str>,
+) -> Result<()> {
+    if branch_exists(branch) {
+        return Ok(());
+    }
+
+    if let Some(remote_ref) = remote
+        && remote_branch_exists(remote_ref)
+    {
+        return Ok(());
+    }
+
+    if create {
+        return Ok(());
+    }
+
+    let config = config::get();
+    let prompt = match (config.create_prompt.as_deref(), base) {
+        (Some(template), _) => fill_placeholders(template, |name| match name {
+            "branch" => Ok(branch.to_string()),
+            "base" => Ok(base.unwrap_or("HEAD").to_string()),
+            _ => Err(unknown_placeholder(
+                name,
+                "create_prompt",
+                &["branch", "base"],
+            )),
+        })?,
+        (None, Some(base)) => format!("Branch '{branch}' does not exist. Create it from '{base}'?"),
+        (None, None) => format!("Branch '{branch}' does not exist. Create it?"),
+    };
+    let should_create = confirm(prompt, config.create_confirm_default.unwrap_or(true))?;
+
+    if should_create {
+        Ok(())
+    } else {
+        anyhow::bail!("Branch '{branch}' does not exist")
+    }
+}
+
+/// Returns the recorded base for a branch when it still resolves to a commit.
+fn recorded_base(branch: &str) -> Option<String> {
+    let entry = state::load_branch_base(branch).ok().flatten()?;
+    if ref_resolves(&entry.base) {
+        Some(entry.base)
+    } else {
+        None
+    }
+}
+
+/// Records the base of a newly created branch, defaulting to the current branch.
+fn record_branch_base(branch: &str, start_point: Option<&str>) {
+    let base = match start_point {
+        Some(start_point) => Some(start_point.to_string()),
+        None => current_branch().ok().flatten(),
+    };
+    let Some(base) = base else {
+        return;
+    };
+    let entry = state::BranchBase {
+        branch: branch.to_string(),
+        base,
+        created_unix: unix_now(),
+    };
+    if let Err(err) = state::save_branch_base(&entry) {
+        warn(format!("failed to record base for '{branch}': {err:#}"));
+    }
+}
+
+/// Returns the current time as seconds since the Unix epoch.
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Returns true if the reference resolves to a commit.
+fn ref_resolves(reference: &str) -> bool {
+    git_command()
+        .args([
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{reference}^{{commit}}"),
+        ])
+        .stdout(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Returns true if the remote branch reference exists.
+fn remote_branch_exists(branch: &str) -> bool {
+    git_command()
+        .args([
+            "show-ref",
+            "--verify",
+            "--quiet",
+            &format!("refs/remotes/{branch}"),
+        ])
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Returns true if the local branch reference exists.
+fn branch_exists(branch: &str) -> bool {
+    git_command()
+        .args([
+            "show-ref",
+            "--verify",
+            "--quiet",
+            &format!("refs/heads/{branch}"),
+        ])
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Builds the target worktree path for a branch.
+///
+/// Without a layout template this is `<root>/<repo>/<branch>`.
+fn worktree_path_for_branch(branch: &str) -> Result<PathBuf> {
+    let Some(layout) = worktree_layout() else {
+        let root = worktree_root()?;
+        let repo = repo_name_stem()?;
+        return Ok(root.join(repo).join(branch));
+    };
+    if !layout.contains("{branch}") {
+        anyhow::bail!("The worktree layout '{layout}' must contain {{branch}}");
+    }
+    expand_worktree_layout(&layout, branch)
+}
+
+/// Reads the layout template from `GWW_WORKTREE_LAYOUT`, then the config file.
+fn worktree_layout() -> Option<String> {
+    env::var("GWW_WORKTREE_LAYOUT")
+        .ok()
+        .or_else(|| config::get().worktree_layout.clone())
+        .filter(|layout| !layout.trim().is_empty())
+}
+
+/// Fills in a layout template, looking up only the placeholders it uses.
+fn expand_worktree_layout(layout: &str, branch: &str) -> Result<PathBuf> {
+    let expanded = expand_layout_template(
+        layout,
+        |name| env::var(name).ok(),
+        |name| match name {
+            "root" => Ok(worktree_root()?.display().to_string()),
+            "owner" => repo_owner(),
+            "repo" => repo_name_stem(),
+            "branch" => Ok(branch.to_string()),
+            _ => Err(unknown_placeholder(
+                name,
+                "the worktree layout",
+                &["root", "owner", "repo", "branch"],
+            )),
+        },
+    )
+    .with_context(|| format!("Invalid worktree layout '{layout}'"))?;
+    let path = PathBuf::from(expanded);
+    if !path.is_absolute() {
+        anyhow::bail!(
+            "The worktree layout '{layout}' expands to the relative path {}; start it with {{root}} or an absolute directory",
+            path.display()
+        );
+    }
+    Ok(path)
+}
+
+/// Expands environment variables, then placeholders, in a layout template.
+///
+/// Variables go first so `${HOME}` is not mistaken for a placeholder, and
+/// their values have braces escaped so a value like `/home/{me}` stays literal.
+fn expand_layout_template(
+    layout: &str,
+    env_lookup: impl Fn(&str) -> Option<String>,
+    value: impl Fn(&str) -> Result<String>,
+) -> Result<String> {
+    let with_env = expand_env_vars(layout, |name| {
+        env_lookup(name).map(|value| value.replace('{', "{{").replace('}', "}}"))
+    })?;
+    fill_placeholders(&with_env, value)
+}
+
+/// Expands `$VAR` and `${VAR}` using `lookup`, failing on undefined variables.
+///
+/// A `$` not followed by a variable name is kept as is.
+fn expand_env_vars(text: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let is_name_char = |ch: char| ch.is_ascii_alphanumeric() || ch == '_';
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, consumed) = if let Some(braced) = after.strip_prefix('{') {
+            let end = braced
+                .find('}')
+                .with_context(|| format!("Unclosed '${{' in '{text}'"))?;
+            (&braced[..end], end + 2)
+        } else {
+            let end = after.find(|ch| !is_name_char(ch)).unwrap_or(after.len());
+            (&after[..end], end)
+        };
+        if name.is_empty() || name.starts_with(|ch: char| ch.is_ascii_digit()) {
+            if consumed > name.len() {
+                anyhow::bail!("Invalid variable name '${{{name}}}' in '{text}'");
+            }
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+        let value =
+            lookup(name).with_context(|| format!("Environment variable ${name} is not set"))?;
+        expanded.push_str(&value);
+        rest = &after[consumed..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Splits a template into literal text and `{name}` placeholders.
+///
+/// Every template gww reads (`--format`, layouts, prompts, scratch names,
+/// launcher commands) goes through here: `{{` and `}}` stand for literal
+/// braces, and an unclosed `{` is an error.
+fn template_parts(template: &str) -> Result<Vec<TemplatePart<'_>>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        if start > 0 {
+            parts.push(TemplatePart::Literal(&rest[..start]));
+        }
+        let brace = &rest[start..];
+        if brace.starts_with("{{") || brace.starts_with("}}") {
+            parts.push(TemplatePart::Literal(&brace[..1]));
+            rest = &brace[2..];
+        } else if let Some(after) = brace.strip_prefix('}') {
+            parts.push(TemplatePart::Literal("}"));
+            rest = after;
+        } else {
+            let Some(end) = brace.find('}') else {
+                anyhow::bail!("Unclosed '{{' in '{template}'");
+            };
+            parts.push(TemplatePart::Placeholder(&brace[1..end]));
+            rest = &brace[end + 1..];
+        }
+    }
+    if !rest.is_empty() {
+        parts.push(TemplatePart::Literal(rest));
+    }
+    Ok(parts)
+}
+
+/// Replaces each `{name}` in `template` with `value(name)`, leaving other text alone.
+fn fill_placeholders(template: &str, value: impl Fn(&str) -> Result<String>) -> Result<String> {
+    let mut filled = String::with_capacity(template.len());
+    for part in template_parts(template)? {
+        match part {
+            TemplatePart::Literal(text) => filled.push_str(text),
+            TemplatePart::Placeholder(name) => filled.push_str(&value(name)?),
+        }
+    }
+    Ok(filled)
+}
+
+/// Builds the error for a placeholder `what` does not know, listing the `known` ones.
+fn unknown_placeholder(name: &str, what: &str, known: &[&str]) -> anyhow::Error {
+    let mut names: Vec<String> = known.iter().map(|known| format!("{{{known}}}")).collect();
+    let last = names.pop().unwrap_or_default();
+    let list = match names.len() {
+        0 => last,
+        1 => format!("{} or {last}", names[0]),
+        _ => format!("{}, or {last}", names.join(", ")),
+    };
+    anyhow::anyhow!("Unknown placeholder {{{name}}} in {what}; use {list}")
+}
+
+/// Returns the directory part of a layout template above its `{branch}` component.
+fn layout_repo_dir(layout: &str) -> &str {
+    let prefix = layout.split("{branch}").next().unwrap_or_default();
+    prefix.rfind('/').map_or("", |end| &prefix[..end])
+}
+
+/// Reads the owner (user, organization, or group path) from the `origin` URL.
+fn repo_owner() -> Result<String> {
+    let url = git_output(["remote", "get-url", "origin"])
+        .context("{owner} in the worktree layout needs an `origin` remote")?;
+    parse_remote_url(url.trim())
+        .map(|remote| remote.owner)
+        .with_context(|| format!("Unable to read an owner from the origin URL {}", url.trim()))
+}
+
+/// Resolves the configured root for worktrees.
+///
+/// A relative `WORKTREE_ROOT` is taken relative to the main worktree, so the
+/// result does not depend on where in the repository gww runs.
+fn worktree_root() -> Result<PathBuf> {
+    if let Ok(root) = env::var("WORKTREE_ROOT") {
+        let root = PathBuf::from(root);
+        if root.is_absolute() {
+            return Ok(root);
+        }
+        return relative_worktree_root(&root, Path::new("."));
+    }
+    let home = env::var("HOME").context("HOME not set")?;
+    Ok(PathBuf::from(home).join("devel").join("worktrees"))
+}
+
+/// Resolves a relative root against the main worktree of the repository containing `dir`.
+///
+/// `dir` is passed to git with `-C` after `--repo`, so `.` means the repository gww runs on.
+fn relative_worktree_root(root: &Path, dir: &Path) -> Result<PathBuf> {
+    let output = git_command()
+        .arg("-C")
+        .arg(dir)
+        .args(["worktree", "list", "--porcelain"])
+        .output()
+        .map_err(git_spawn_error)?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "A relative WORKTREE_ROOT needs a git repository to resolve against: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let main = parse_worktree_porcelain(&String::from_utf8_lossy(&output.stdout))
+        .into_iter()
+        .find(|wt| wt.is_main)
+        .context("Unable to find the main worktree to resolve WORKTREE_ROOT")?;
+    Ok(resolve_relative_root(&main.path, root))
+}
+
+/// Joins a relative root onto `base`, folding `.` and `..` components.
+fn resolve_relative_root(base: &Path, root: &Path) -> PathBuf {
+    let mut resolved = base.to_path_buf();
+    for component in root.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                resolved.pop();
+            }
+            other => resolved.push(other),
+        }
+    }
+    resolved
+}
+
+/// Resolves the repository name for worktree paths.
+fn repo_name_stem() -> Result<String> {
+    if let Ok(url) = git_output(["remote", "get-url", "origin"])
+        && let Some(stem) = repo_name_from_url(url.trim())
+    {
+        return Ok(stem);
+    }
+    let root = git_output(["rev-parse", "--show-toplevel"])?;
+    let path = Path::new(root.trim());
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.to_string())
+        .context("Unable to determine repository name")
+}
+
+/// Extracts the repository name from a git remote URL.
+fn repo_name_from_url(url: &str) -> Option<String> {
+    let cleaned = url.trim_end_matches('/');
+    let name = cleaned.rsplit('/').next()?;
+    Some(name.trim_end_matches(".git").to_string())
+}
+
+/// Splits an HTTPS, SSH, or scp-style remote URL into host, owner, and name.
+fn parse_remote_url(url: &str) -> Option<RemoteRepo> {
+    let url = url.trim().trim_end_matches('/');
+    let (host, path) = if let Some((_, rest)) = url.split_once("://") {
+        let (authority, path) = rest.split_once('/')?;
+        let host = authority.rsplit('@').next()?;
+        (host.split(':').next()?, path)
+    } else {
+        let (authority, path) = url.split_once(':')?;
+        (authority.rsplit('@').next()?, path)
+    };
+    let path = path.trim_start_matches('/').trim_end_matches(".git");
+    let (owner, name) = path.rsplit_once('/')?;
+    if host.is_empty() || owner.is_empty() || name.is_empty() {
+        return None;
+    }
+    Some(RemoteRepo {
+        host: host.to_string(),
+        owner: owner.to_string(),
+        name: name.to_string(),
+    })
+}
+
+/// Runs `git worktree add` with optional branch creation from a start point.
+///
+/// A start point without a branch adds a detached worktree at that commit.
+fn git_worktree_add(
+    path: &Path,
+    branch: Option<&str>,
+    start_point: Option<&str>,
+    no_checkout: bool,
+) -> Result<()> {
+    let mut cmd = git_command();
+    cmd.arg("worktree").arg("add");
+    if no_checkout {
+        cmd.arg("--no-checkout");
+    }
+    cmd.arg(path);
+
+    let mut created_branch = None;
+    if let Some(start_point) = start_point {
+        match branch {
+            Some(local_branch) => {
+                cmd.arg("-b").arg(local_branch).arg(start_point);
+                created_branch = Some(local_branch);
+            }
+            None => {
+                cmd.arg("--detach").arg(start_point);
+            }
+        }
+    } else if let Some(branch) = branch {
+        if branch_exists(branch) {
+            cmd.arg(branch);
+        } else if !ref_resolves("HEAD") {
+            ensure_orphan_worktree_supported()?;
+            verbose(format!(
+                "HEAD has no commits; creating '{branch}' as an orphan"
+            ));
+            cmd.arg("--orphan").arg("-b").arg(branch);
+        } else {
+            cmd.arg("-b").arg(branch);
+            created_branch = Some(branch);
+        }
+    }
+
+    if PRINT_CMD.load(Ordering::Relaxed) {
+        eprintln!("{}", shell_command_line(&cmd));
+    }
+    let parent = path.parent().unwrap_or(Path::new(""));
+    with_created_dirs(parent, || run_worktree_add(&mut cmd, path, branch))?;
+    if let Some(created_branch) = created_branch {
+        record_branch_base(created_branch, start_point);
+    }
+    Ok(())
+}
+
+/// Fails with a clear message when git cannot add a worktree for an unborn branch.
+fn ensure_orphan_worktree_supported() -> Result<()> {
+    if git_version()? < ORPHAN_WORKTREE_GIT_VERSION {
+        let GitVersion(major, minor, patch) = ORPHAN_WORKTREE_GIT_VERSION;
+        anyhow::bail!(
+            "This repository has no commits yet; adding a worktree before the first commit needs git {major}.{minor}.{patch} or newer. Commit once in the main worktree first"
+        );
+    }
+    Ok(())
+}
+
+/// Runs a prepared `git worktree add`, surfacing git's stderr when it fails.
+///
+/// The attempt is recorded in the audit log either way.
+fn run_worktree_add(cmd: &mut Command, path: &Path, branch: Option<&str>) -> Result<()> {
+    let quiet = QUIET_GIT.load(Ordering::Relaxed);
+    let output = cmd
+        .stdout(if quiet {
+            Stdio::null()
+        } else {
+            Stdio::inherit()
+        })
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(git_spawn_error)
+        .context("Failed to run git worktree add")?;
+    audit::record(audit::Operation::Add, branch, path, output.status.code());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        let trimmed = stderr.trim();
+        if trimmed.is_empty() {
+            anyhow::bail!("git worktree add failed");
+        }
+        if let Some(path) = checked_out_path(trimmed) {
+            return Err(CheckedOutElsewhere {
+                path,
+                message: trimmed.to_string(),
+            }
+            .into());
+        }
+        anyhow::bail!("git worktree add failed: {trimmed}");
+    }
+    if !quiet {
+        eprint!("{stderr}");
+    }
+    Ok(())
+}
+
+/// Creates `dir` and runs `action`, removing directories it created if `action` fails.
+///
+/// Only directories that did not exist beforehand and are still empty are removed.
+/// `git worktree add` receives Ctrl-C too, so an interrupt lands here as a failure.
+fn with_created_dirs<F>(dir: &Path, action: F) -> Result<()>
+where
+    F: FnOnce() -> Result<()>,
+{
+    let created: Vec<PathBuf> = dir
+        .ancestors()
+        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+        .take_while(|ancestor| !ancestor.exists())
+        .map(Path::to_path_buf)
+        .collect();
+    if !created.is_empty() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+
+    CHILD_OWNS_INTERRUPT.store(true, Ordering::SeqCst);
+    let result = action();
+    CHILD_OWNS_INTERRUPT.store(false, Ordering::SeqCst);
+    if result.is_err() {
+        remove_empty_dirs(&created);
+    }
+    result
+}
+
+/// Restores the cursor and exits when the user presses Ctrl-C at a prompt.
+///
+/// While a git child runs it receives the same signal and gww waits for it to
+/// exit, so directory cleanup is left to [`with_created_dirs`].
+fn install_interrupt_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if CHILD_OWNS_INTERRUPT.load(Ordering::SeqCst) {
+            return;
+        }
+        let _ = console::Term::stderr().show_cursor();
+        std::process::exit(130);
+    })
+    .context("Failed to install Ctrl-C handler")
+}
+
+/// Removes directories innermost first, stopping at the first one that is not empty.
+fn remove_empty_dirs(dirs: &[PathBuf]) {
+    for dir in dirs {
+        if fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
+
+/// Returns sparse-checkout patterns from the command line, falling back to config.
+fn sparse_patterns(args: &CheckoutArgs) -> Vec<String> {
+    if args.sparse.is_empty() {
+        config::get().sparse_patterns.clone()
+    } else {
+        args.sparse.clone()
+    }
+}
+
+/// Sets each `[worktree_config]` entry in the new worktree's own config, warning on failures.
+///
+/// Uses `git config --worktree`, so the settings do not leak into the main
+/// worktree or other worktrees.
+fn apply_worktree_config(path: &Path) {
+    let entries = &config::get().worktree_config;
+    if entries.is_empty() {
+        return;
+    }
+    if let Err(err) = enable_worktree_config() {
+        warn(format!("skipping [worktree_config]: {err:#}"));
+        return;
+    }
+    for (key, value) in entries {
+        verbose(format!(
+            "Setting git config {key}={value} in {}",
+            path.display()
+        ));
+        let output = git_command()
+            .arg("-C")
+            .arg(path)
+            .args(["config", "--worktree", key, value])
+            .output();
+        match output {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => warn(format!(
+                "failed to set git config {key}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+            Err(err) => warn(format!("failed to run git config: {err}")),
+        }
+    }
+}
+
+/// Turns on `extensions.worktreeConfig` so worktrees can hold their own config.
+///
+/// Refuses when the shared config sets `core.bare = true` or `core.worktree`,
+/// which git expects to be moved to the main worktree's config first.
+fn enable_worktree_config() -> Result<()> {
+    if git_version()? < WORKTREE_CONFIG_GIT_VERSION {
+        let GitVersion(major, minor, patch) = WORKTREE_CONFIG_GIT_VERSION;
+        anyhow::bail!("per-worktree config needs git {major}.{minor}.{patch} or newer");
+    }
+    let shared = |key: &str| {
+        git_output(["config", "--local", "--get", key]).map(|value| value.trim().to_string())
+    };
+    if shared("extensions.worktreeConfig").is_ok_and(|value| value == "true") {
+        return Ok(());
+    }
+    if shared("core.bare").is_ok_and(|value| value == "true") || shared("core.worktree").is_ok() {
+        anyhow::bail!(
+            "the shared git config sets core.bare or core.worktree; move it to config.worktree before enabling extensions.worktreeConfig"
+        );
+    }
+    verbose("Enabling extensions.worktreeConfig".to_string());
+    git_output(["config", "--local", "extensions.worktreeConfig", "true"])
+        .context("Failed to enable extensions.worktreeConfig")?;
+    Ok(())
+}
+
+/// Locks a worktree so `git worktree prune` leaves it alone, recording a reason if given.
+fn lock_worktree(path: &Path, reason: &str) -> Result<()> {
+    if git_version()? < WORKTREE_LOCK_GIT_VERSION {
+        let GitVersion(major, minor, patch) = WORKTREE_LOCK_GIT_VERSION;
+        anyhow::bail!("git worktree lock requires git {major}.{minor}.{patch} or newer");
+    }
+    let mut cmd = git_command();
+    cmd.args(["worktree", "lock"]);
+    if !reason.is_empty() {
+        cmd.arg("--reason").arg(reason);
+    }
+    let status = cmd
+        .arg(path)
+        .status()
+        .map_err(git_spawn_error)
+        .context("Failed to run git worktree lock")?;
+    if !status.success() {
+        anyhow::bail!("git worktree lock failed");
+    }
+    Ok(())
+}
+
+/// Copies a template directory's contents into a worktree.
+///
+/// Existing files are kept unless `overwrite` is set; directories are merged.
+/// Symlinks are recreated rather than followed.
+fn copy_template(template: &Path, dest: &Path, overwrite: bool) -> Result<()> {
+    let entries =
+        fs::read_dir(template).with_context(|| format!("Failed to read {}", template.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let source = entry.path();
+        let target = dest.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            fs::create_dir_all(&target)
+                .with_context(|| format!("Failed to create {}", target.display()))?;
+            copy_template(&source, &target, overwrite)?;
+            continue;
+        }
+        if target.symlink_metadata().is_ok() {
+            if !overwrite {
+                verbose(format!("Keeping existing {}", target.display()));
+                continue;
+            }
+            fs::remove_file(&target)
+                .with_context(|| format!("Failed to replace {}", target.display()))?;
+        }
+        if file_type.is_symlink() {
+            copy_symlink(&source, &target)?;
+        } else {
+            fs::copy(&source, &target)
+                .with_context(|| format!("Failed to copy {}", source.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Recreates a symlink at `target` pointing where `source` points.
+#[cfg(unix)]
+fn copy_symlink(source: &Path, target: &Path) -> Result<()> {
+    let link = fs::read_link(source)
+        .with_context(|| format!("Failed to read link {}", source.display()))?;
+    std::os::unix::fs::symlink(link, target)
+        .with_context(|| format!("Failed to create link {}", target.display()))
+}
+
+/// Copies the file a symlink points to where symlinks are not portable.
+#[cfg(not(unix))]
+fn copy_symlink(source: &Path, target: &Path) -> Result<()> {
+    fs::copy(source, target).with_context(|| format!("Failed to copy {}", source.display()))?;
+    Ok(())
+}
+
+/// Restricts a new worktree to the given cone-mode sparse-checkout patterns.
+fn apply_sparse_checkout(path: &Path, patterns: &[String]) -> Result<()> {
+    if patterns.is_empty() {
+        return Ok(());
+    }
+    if git_version()? < SPARSE_CHECKOUT_GIT_VERSION {
+        let GitVersion(major, minor, patch) = SPARSE_CHECKOUT_GIT_VERSION;
+        warn(format!(
+            "sparse-checkout requires git {major}.{minor}.{patch} or newer; skipping"
+        ));
+        return Ok(());
+    }
+
+    let status = git_command()
+        .arg("-C")
+        .arg(path)
+        .args(["sparse-checkout", "init", "--cone"])
+        .status()
+        .map_err(git_spawn_error)
+        .context("Failed to run git sparse-checkout init")?;
+    if !status.success() {
+        anyhow::bail!("git sparse-checkout init failed");
+    }
+
+    let status = git_command()
+        .arg("-C")
+        .arg(path)
+        .args(["sparse-checkout", "set"])
+        .args(patterns)
+        .status()
+        .map_err(git_spawn_error)
+        .context("Failed to run git sparse-checkout set")?;
+    if !status.success() {
+        anyhow::bail!("git sparse-checkout set failed");
+    }
+    Ok(())
+}
+
+/// Returns true when a missing branch is created without asking.
+///
+/// `-b` and `--no-create` win over `GWW_DEFAULT_CREATE`, which wins over the
+/// config file. Only consulted where gww would otherwise prompt, so a default
+/// of creating never skips worktree shorthand or the missing-remote policy.
+fn should_create_branches(args: &CheckoutArgs) -> bool {
+    let configured = env::var("GWW_DEFAULT_CREATE")
+        .ok()
+        .map(|value| env_flag(&value))
+        .unwrap_or(config::get().default_create);
+    resolve_toggle(args.create, args.no_create, Some(configured), false)
+}
+
+/// Reads an on/off environment value; `1`, `true`, `yes`, and `on` turn it on.
+fn env_flag(value: &str) -> bool {
+    matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "1" | "true" | "yes" | "on"
+    )
+}
+
+/// Returns true when submodules should be initialized after checkout.
+///
+/// The flags win over `GWW_SUBMODULE_ON_CHECKOUT`, which wins over the config file.
+fn should_init_submodules(args: &CheckoutArgs) -> bool {
+    if args.submodules || args.no_submodules {
+        return args.submodules;
+    }
+    env::var_os("GWW_SUBMODULE_ON_CHECKOUT").is_some() || config::get().submodules
+}
+
+/// Initializes submodules recursively for a worktree path.
+///
+/// Git's stdout goes to stderr so the autocd wrapper still shows its progress.
+fn init_submodules(path: &Path) -> Result<()> {
+    let status = git_command()
+        .arg("-C")
+        .arg(path)
+        .args(["submodule", "update", "--init", "--recursive"])
+        .stdout(std::io::stderr())
+        .status()
+        .map_err(git_spawn_error)
+        .context("Failed to run git submodule update")?;
+    if !status.success() {
+        anyhow::bail!("git submodule update failed");
+    }
+    Ok(())
+}
+
+/// Runs `git worktree remove` for the selected path.
+fn git_worktree_remove(path: &Path, branch: Option<&str>, force: bool) -> Result<()> {
+    if !force {
+        let output = run_worktree_remove(path, branch, false)?;
+        if output.status.success() {
+            return Ok(());
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let trimmed = stderr.trim();
+        let message = if trimmed.is_empty() {
+            "git worktree remove failed"
+        } else {
+            trimmed
+        };
+
+        let should_remove = confirm(
+            format!("{message}. Force remove anyway? This will delete the worktree from disk."),
+            false,
+        )?;
+        if !should_remove {
+            anyhow::bail!("Worktree removal cancelled");
+        }
+    }
+
+    let output = run_worktree_remove(path, branch, true)?;
+    if !output.status.success() {
+        anyhow::bail!("git worktree remove failed");
+    }
+    Ok(())
+}
+
+/// Runs `git worktree remove` once and records the attempt in the audit log.
+///
+/// Output is captured for the first, unforced attempt so its error can be
+/// shown in the force prompt; forced removals print straight to the terminal.
+/// The root lock is held for this command only, not across the prompt.
+fn run_worktree_remove(path: &Path, branch: Option<&str>, force: bool) -> Result<Output> {
+    let _lock = acquire_root_lock();
+    let mut cmd = git_command();
+    cmd.args(["worktree", "remove"]);
+    if force {
+        cmd.arg("--force")
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit());
+    }
+    let output = cmd
+        .arg(path)
+        .output()
+        .map_err(git_spawn_error)
+        .context("Failed to run git worktree remove")?;
+    audit::record(audit::Operation::Remove, branch, path, output.status.code());
+    Ok(output)
+}
+
+/// Drops git's entry for a worktree whose directory no longer exists.
+fn remove_missing_worktree(path: &Path) -> Result<()> {
+    let _lock = acquire_root_lock();
+    let removed = git_command()
+        .args(["worktree", "remove", "--force"])
+        .arg(path)
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+    if removed {
+        return Ok(());
+    }
+
+    let status = git_command()
+        .args(["worktree", "prune"])
+        .status()
+        .map_err(git_spawn_error)
+        .context("Failed to run git worktree prune")?;
+    if !status.success() {
+        anyhow::bail!("git worktree prune failed");
+    }
+    let worktrees = list_worktrees_info()?;
+    let Some(listed) = worktrees.iter().find(|worktree| worktree.path == path) else {
+        return Ok(());
+    };
+    match listed.locked.as_deref() {
+        Some("") => anyhow::bail!(
+            "Could not remove stale worktree {}; it is locked (see `git worktree unlock`)",
+            path.display()
+        ),
+        Some(reason) => anyhow::bail!(
+            "Could not remove stale worktree {}; it is locked: {reason} (see `git worktree unlock`)",
+            path.display()
+        ),
+        None => anyhow::bail!(
+            "Could not remove stale worktree {}; it may be locked (see `git worktree unlock`)",
+            path.display()
+        ),
+    }
+}
+
+/// Emits a tagged absolute path for shell auto-cd scripts and records the visit.
+///
+/// Warns when the marker goes straight to a terminal, which means the binary
+/// was run without the sourced wrapper and no `cd` will happen.
+fn emit_cd(path: &Path, branch: Option<&str>) {
+    let base = working_dir().unwrap_or_default();
+    let path = absolute_cd_path(&base, path);
+    let visit = state::Visit {
+        branch: branch.unwrap_or_default().to_string(),
+        path,
+        visited_unix: unix_now(),
+    };
+    if let Err(err) = state::record_visit(&visit) {
+        verbose(format!("Could not record visit: {err:#}"));
+    }
+    if let Some(launcher) = LAUNCHER.get() {
+        match launch_worktree(launcher, &visit.path, branch) {
+            Ok(()) => return,
+            Err(err) => warn(format!("{err:#}; changing directory instead")),
+        }
+    }
+    println!("{}{}", cd_prefix(), visit.path.display());
+    if !shell_integration_active() && console::Term::stdout().is_term() {
+        eprintln!(
+            "Warning: the gww shell function is not active, so your shell will not cd; add `source <(gww autocd)` to your shell config"
+        );
+    }
+}
+
+/// Picks where checkout opens the worktree: the flags win over `open_in` in the config.
+///
+/// Fails before anything is created when tmux is requested outside a tmux
+/// session or the terminal launcher is not configured.
+fn resolve_launcher(args: &CheckoutArgs) -> Result<Option<Launcher>> {
+    let config = config::get();
+    let open_in = if args.tmux {
+        config::OpenIn::Tmux
+    } else if args.terminal {
+        config::OpenIn::Terminal
+    } else if args.cd {
+        config::OpenIn::Cd
+    } else {
+        config.open_in
+    };
+    match open_in {
+        config::OpenIn::Cd => Ok(None),
+        config::OpenIn::Tmux => {
+            if env::var_os("TMUX").is_none_or(|value| value.is_empty()) {
+                anyhow::bail!(
+                    "Not inside a tmux session (TMUX is not set); start tmux first, or pass --cd or --terminal"
+                );
+            }
+            Ok(Some(Launcher::Tmux))
+        }
+        config::OpenIn::Terminal => match config.terminal_command.as_deref() {
+            Some(command) if !command.trim().is_empty() => {
+                Ok(Some(Launcher::Terminal(command.to_string())))
+            }
+            _ => anyhow::bail!(
+                "No terminal launcher configured; set terminal_command in the config, e.g. terminal_command = \"kitty --directory {{path}}\""
+            ),
+        },
+    }
+}
+
+/// Opens `path` with the launcher, detached from gww's output.
+fn launch_worktree(launcher: &Launcher, path: &Path, branch: Option<&str>) -> Result<()> {
+    let name = branch
+        .map(str::to_string)
+        .or_else(|| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_default();
+    let argv = match launcher {
+        Launcher::Tmux => vec![
+            "tmux".to_string(),
+            "new-window".to_string(),
+            "-c".to_string(),
+            path.display().to_string(),
+            "-n".to_string(),
+            name,
+        ],
+        Launcher::Terminal(template) => launcher_args(template, path, &name)?,
+    };
+    let Some((program, rest)) = argv.split_first() else {
+        anyhow::bail!("terminal_command is empty");
+    };
+    verbose(format!("Opening worktree with: {}", argv.join(" ")));
+    let mut cmd = Command::new(program);
+    cmd.args(rest)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // tmux returns once the window exists; terminals keep running, so they are not waited on.
+    if matches!(launcher, Launcher::Tmux) {
+        let status = cmd
+            .status()
+            .with_context(|| format!("Failed to run {program}"))?;
+        if !status.success() {
+            anyhow::bail!("{program} exited with {status}");
+        }
+    } else {
+        cmd.spawn()
+            .with_context(|| format!("Failed to run {program}"))?;
+    }
+    Ok(())
+}
+
+/// Splits a launcher template on whitespace and fills in `{path}` and `{branch}`.
+///
+/// The template is not run through a shell, so substituted values need no quoting.
+fn launcher_args(template: &str, path: &Path, branch: &str) -> Result<Vec<String>> {
+    let path = path.display().to_string();
+    template
+        .split_whitespace()
+        .map(|word| {
+            fill_placeholders(word, |name| match name {
+                "path" => Ok(path.clone()),
+                "branch" => Ok(branch.to_string()),
+                _ => Err(unknown_placeholder(
+                    name,
+                    "terminal_command",
+                    &["path", "branch"],
+                )),
+            })
+        })
+        .collect()
+}
+
+/// Returns true when running under the function generated by `gww autocd`.
+///
+/// `GWW_AUTOCD` is what wrappers generated by older versions set.
+fn shell_integration_active() -> bool {
+    env::var_os("GWW_SHELL_INTEGRATION").is_some() || env::var_os("GWW_AUTOCD").is_some()
+}
+
+/// Resolves `path` against `base` (where git ran), canonicalizing when it exists.
+///
+/// Paths that cannot be canonicalized, such as ones not created yet, are
+/// returned joined to `base` without resolving symlinks or `..`.
+fn absolute_cd_path(base: &Path, path: &Path) -> PathBuf {
+    let joined = base.join(path);
+    fs::canonicalize(&joined).unwrap_or(joined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Substitutes placeholders inside words without splitting the values.
+    #[test]
+    fn launcher_args_fill_placeholders() {
+        let args = launcher_args(
+            "wezterm start --cwd {path} --class gww-{branch}",
+            Path::new("/wt/my repo/feat"),
+            "feat/x",
+        )
+        .expect("known placeholders");
+
+        assert_eq!(
+            args,
+            vec![
+                "wezterm",
+                "start",
+                "--cwd",
+                "/wt/my repo/feat",
+                "--class",
+                "gww-feat/x"
+            ]
+        );
+        assert!(launcher_args("term {cwd}", Path::new("/wt"), "x").is_err());
+    }
+
+    /// Puts a divider before each group only when several groups are listed.
+    #[test]
+    fn selector_entries_divide_groups() {
+        use BranchSource::{Local, Remote, Worktree};
+
+        assert_eq!(
+            selector_entries(&[Worktree, Worktree, Local, Remote], true),
+            vec![
+                SelectorEntry::Header(Worktree),
+                SelectorEntry::Branch(0),
+                SelectorEntry::Branch(1),
+                SelectorEntry::Header(Local),
+                SelectorEntry::Branch(2),
+                SelectorEntry::Header(Remote),
+                SelectorEntry::Branch(3),
+                SelectorEntry::Create,
+            ]
+        );
+        assert_eq!(
+            selector_entries(&[Worktree, Worktree], true),
+            vec![
+                SelectorEntry::Branch(0),
+                SelectorEntry::Branch(1),
+                SelectorEntry::Create,
+            ]
+        );
+        assert_eq!(
+            selector_entries(&[Worktree, Local], false),
+            vec![
+                SelectorEntry::Branch(0),
+                SelectorEntry::Branch(1),
+                SelectorEntry::Create,
+            ]
+        );
+    }
+
+    /// Accepts the usual truthy spellings and treats anything else as off.
+    #[test]
+    fn env_flag_reads_truthy_values() {
+        for value in ["1", "true", "TRUE", " yes ", "on"] {
+            assert!(env_flag(value), "{value}");
+        }
+        for value in ["", "0", "false", "no", "off", "maybe"] {
+            assert!(!env_flag(value), "{value}");
+        }
+    }
+
+    /// Verifies the sort order and de-duplication behavior.
+    #[test]
+    fn sort_by_recent_orders_by_timestamp_and_dedups() {
+        let mut meta = HashMap::new();
+        meta.insert(
+            "main".to_string(),
+            BranchMeta {
+                timestamp_unix: 200,
+                summary: placeholder_summary(),
+            },
+        );
+        meta.insert(
+            "feature".to_string(),
+            BranchMeta {
+                timestamp_unix: 100,
+                summary: placeholder_summary(),
+            },
+        );
+        meta.insert(
+            "hotfix".to_string(),
+            BranchMeta {
+                timestamp_unix: 200,
+                summary: placeholder_summary(),
+            },
+        );
+
+        let ordered = sort_by_recent(
+            ["feature", "main", "feature", "hotfix"],
+            &meta,
+            TieBreak::NameAsc,
+        );
+
+        assert_eq!(ordered, vec!["hotfix", "main", "feature"]);
+    }
+
+    /// Builds metadata entries with the given timestamps.
+    fn meta_with_timestamps(entries: &[(&str, i64)]) -> HashMap<String, BranchMeta> {
+        entries
+            .iter()
+            .map(|(name, timestamp_unix)| {
+                (
+                    name.to_string(),
+                    BranchMeta {
+                        timestamp_unix: *timestamp_unix,
+                        summary: placeholder_summary(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Applies each tie-break mode to equal timestamps.
+    #[test]
+    fn sort_by_recent_applies_configured_tie_break() {
+        let meta = meta_with_timestamps(&[("b", 5), ("c", 5), ("a", 5), ("new", 9)]);
+        let names = ["b", "c", "a", "new"];
+
+        assert_eq!(
+            sort_by_recent(names, &meta, TieBreak::NameAsc),
+            vec!["new", "a", "b", "c"]
+        );
+        assert_eq!(
+            sort_by_recent(names, &meta, TieBreak::NameDesc),
+            vec!["new", "c", "b", "a"]
+        );
+        assert_eq!(
+            sort_by_recent(names, &meta, TieBreak::Insertion),
+            vec!["new", "b", "c", "a"]
+        );
+    }
+
+    /// Places branches without metadata last and alphabetically.
+    #[test]
+    fn sort_by_recent_puts_missing_metadata_last() {
+        let meta = meta_with_timestamps(&[("old", 0), ("recent", 10)]);
+
+        let ordered = sort_by_recent(
+            ["zeta", "old", "alpha", "recent"],
+            &meta,
+            TieBreak::NameDesc,
+        );
+
+        assert_eq!(ordered, vec!["recent", "old", "alpha", "zeta"]);
+    }
+
+    /// Renders just the source tag and branch name without a summary.
+    #[test]
+    fn format_branch_item_without_summary_shows_only_name() {
+        let info = BranchInfo {
+            name: "idle".to_string(),
+            source: BranchSource::Local,
+            summary: placeholder_summary(),
+            is_current: false,
+            timestamp_unix: None,
+        };
+        let options = CandidateOptions {
+            no_summary: true,
+            ..CandidateOptions::default()
+        };
+
+        assert_eq!(
+            console::strip_ansi_codes(&format_branch_item(&info, &options)),
+            "[L ] idle"
+        );
+    }
+
+    /// Falls back to the tie-break when every timestamp is zero.
+    #[test]
+    fn sort_by_recent_handles_all_zero_timestamps() {
+        let meta = meta_with_timestamps(&[("b", 0), ("a", 0), ("c", 0)]);
+        let empty = HashMap::new();
+
+        assert_eq!(
+            sort_by_recent(["b", "a", "c"], &meta, TieBreak::Insertion),
+            vec!["b", "a", "c"]
+        );
+        assert_eq!(
+            sort_by_recent(["b", "a", "c"], &empty, TieBreak::Insertion),
+            vec!["a", "b", "c"]
+        );
+    }
+
+    /// Ensures remote prefixes are stripped correctly.
+    #[test]
+    fn strip_remote_prefix_handles_remote_and_local_names() {
+        assert_eq!(strip_remote_prefix("origin/main"), "main");
+        assert_eq!(strip_remote_prefix("main"), "main");
+    }
+
+    /// Accepts a single external argument as a branch name.
+    #[test]
+    fn branch_from_external_args_accepts_branch() {
+        let args = vec!["main".to_string()];
+
+        assert_eq!(branch_from_external_args(&args), Some("main".to_string()));
+    }
+
+    /// Rejects flags or extra arguments as checkout branch names.
+    #[test]
+    fn branch_from_external_args_rejects_flags_and_extra_args() {
+        let flags = vec!["-h".to_string()];
+        let extra = vec!["main".to_string(), "extra".to_string()];
+
+        assert_eq!(branch_from_external_args(&flags), None);
+        assert_eq!(branch_from_external_args(&extra), None);
+    }
+
+    /// Resolves the default command by name or alias and rejects unknown or incomplete ones.
+    #[test]
+    fn default_command_accepts_known_commands_only() {
+        assert!(matches!(
+            default_command("checkout"),
+            Ok(Commands::Checkout(_))
+        ));
+        assert!(matches!(default_command("ls"), Ok(Commands::List(_))));
+        assert!(default_command("main").is_err());
+        assert!(default_command("timechooser").is_err());
+        assert!(default_command("exists").is_err());
+    }
+
+    /// Checks matching behavior for remote branches.
+    #[test]
+    fn match_remote_branch_prefers_exact_match() {
+        let remotes = vec!["origin/main".to_string(), "upstream/dev".to_string()];
+
+        assert_eq!(
+            match_remote_branch("origin/main", &remotes),
+            Some("origin/main".to_string())
+        );
+        assert_eq!(
+            match_remote_branch("dev", &remotes),
+            Some("upstream/dev".to_string())
+        );
+        assert_eq!(match_remote_branch("missing", &remotes), None);
+    }
+
+    /// Pads the status column and indents continuation lines of the detail.
+    #[test]
+    fn batch_status_line_aligns_and_indents() {
+        assert_eq!(
+            batch_status_line("created", "feat", "/wt/feat"),
+            "created  feat\t/wt/feat"
+        );
+        assert_eq!(
+            batch_status_line(
+                "failed",
+                "a..b",
+                "git worktree add failed: Preparing\nfatal: bad\n"
+            ),
+            "failed   a..b\tgit worktree add failed: Preparing\n\tfatal: bad"
+        );
+    }
+
+    /// Picks the reason and file out of repair lines and skips anything else.
+    #[test]
+    fn parse_repair_output_reads_reason_and_file() {
+        let output = "repair: gitdir incorrect: /repo/.git/worktrees/feat/gitdir\n\
+                      warning: something else\n\
+                      repair: .git file broken: /wt/fix\n";
+
+        assert_eq!(
+            parse_repair_output(output),
+            vec![
+                (
+                    "gitdir incorrect".to_string(),
+                    "/repo/.git/worktrees/feat/gitdir".to_string()
+                ),
+                (".git file broken".to_string(), "/wt/fix".to_string()),
+            ]
+        );
+    }
+
+    /// Lists every remote with the branch, origin first, unless a full ref is given.
+    #[test]
+    fn remote_branch_matches_puts_origin_first() {
+        let remotes = vec![
+            "fork/feature".to_string(),
+            "origin/feature".to_string(),
+            "upstream/feature".to_string(),
+            "upstream/other".to_string(),
+        ];
+
+        assert_eq!(
+            remote_branch_matches("feature", &remotes),
+            vec!["origin/feature", "fork/feature", "upstream/feature"]
+        );
+        assert_eq!(
+            remote_branch_matches("upstream/feature", &remotes),
+            vec!["upstream/feature"]
+        );
+        assert!(remote_branch_matches("missing", &remotes).is_empty());
+    }
+
+    /// Prefers exact, then unique prefix, then unique substring worktree matches.
+    #[test]
+    fn resolve_worktree_shorthand_orders_match_kinds() {
+        let worktrees: Vec<WorktreeInfo> = ["feat", "feature/login", "fix/login-typo", "docs"]
+            .iter()
+            .map(|name| WorktreeInfo {
+                path: PathBuf::from(format!("/tmp/{name}")),
+                branch: Some(name.to_string()),
+                head: None,
+                is_main: false,
+                ..WorktreeInfo::default()
+            })
+            .collect();
+        let resolve = |name| resolve_worktree_shorthand(&worktrees, name);
+
+        assert_eq!(resolve("feat").expect("exact").as_deref(), Some("feat"));
+        assert_eq!(resolve("do").expect("prefix").as_deref(), Some("docs"));
+        assert_eq!(
+            resolve("typo").expect("substring").as_deref(),
+            Some("fix/login-typo")
+        );
+        assert_eq!(resolve("nothing").expect("no match"), None);
+        assert!(resolve("fe").is_err());
+        assert!(resolve("login").is_err());
+    }
+
+    /// Distinguishes worktrees on disk, bare branches, and unknown names.
+    #[test]
+    fn branch_state_classifies_worktrees_and_branches() {
+        let worktrees = vec![
+            WorktreeInfo {
+                path: env::temp_dir(),
+                branch: Some("feature".to_string()),
+                head: None,
+                is_main: false,
+                ..WorktreeInfo::default()
+            },
+            WorktreeInfo {
+                path: PathBuf::from("/nonexistent/gww/gone"),
+                branch: Some("gone".to_string()),
+                head: None,
+                is_main: false,
+                ..WorktreeInfo::default()
+            },
+        ];
+        let locals = vec![
+            "feature".to_string(),
+            "gone".to_string(),
+            "idle".to_string(),
+        ];
+        let remotes = vec![
+            "origin/feature".to_string(),
+            "origin/remote-only".to_string(),
+        ];
+        let state = |branch| branch_state(&worktrees, &locals, &remotes, branch);
+
+        assert_eq!(state("feature"), BranchState::Worktree(env::temp_dir()));
+        assert_eq!(
+            state("origin/feature"),
+            BranchState::Worktree(env::temp_dir())
+        );
+        assert_eq!(state("gone"), BranchState::BranchOnly);
+        assert_eq!(state("idle"), BranchState::BranchOnly);
+        assert_eq!(state("remote-only"), BranchState::BranchOnly);
+        assert_eq!(state("nope"), BranchState::Missing);
+    }
+
+    /// Accepts plain directory names and rejects nested or relative paths.
+    #[test]
+    fn validate_dir_name_requires_single_component() {
+        assert_eq!(validate_dir_name("short").ok(), Some("short"));
+        assert!(validate_dir_name("a/b").is_err());
+        assert!(validate_dir_name("..").is_err());
+        assert!(validate_dir_name("").is_err());
+        assert!(validate_dir_name("/abs").is_err());
+    }
+
+    /// Resolves relative roots against the main worktree rather than the current subdirectory.
+    #[test]
+    fn resolve_relative_root_uses_main_worktree() {
+        let worktrees = parse_worktree_porcelain(
+            "worktree /src/repo\nbranch refs/heads/main\n\nworktree /wt/repo/feature\nbranch refs/heads/feature\n",
+        );
+        let main = worktrees
+            .iter()
+            .find(|wt| wt.is_main)
+            .expect("main worktree");
+
+        assert_eq!(
+            resolve_relative_root(&main.path, Path::new("../worktrees")),
+            PathBuf::from("/src/worktrees")
+        );
+        assert_eq!(
+            resolve_relative_root(&main.path, Path::new("./.worktrees/./x")),
+            PathBuf::from("/src/repo/.worktrees/x")
+        );
+    }
+
+    /// Resolves a relative root from a nested directory the same as from the top.
+    #[test]
+    fn relative_worktree_root_ignores_the_subdirectory() {
+        let base = unique_temp_dir("relative-root");
+        let repo = base.join("repo");
+        let nested = repo.join("src").join("deep");
+        fs::create_dir_all(&nested).expect("create dirs");
+        let init = Command::new("git")
+            .args(["init", "--quiet"])
+            .arg(&repo)
+            .status()
+            .expect("run git init");
+        assert!(init.success());
+        let expected = fs::canonicalize(&base)
+            .expect("canonical base")
+            .join("worktrees");
+
+        let from_nested =
+            relative_worktree_root(Path::new("../worktrees"), &nested).expect("from nested");
+        let from_top = relative_worktree_root(Path::new("../worktrees"), &repo).expect("from top");
+
+        assert_eq!(from_nested, expected);
+        assert_eq!(from_top, expected);
+        fs::remove_dir_all(&base).expect("cleanup");
+    }
+
+    /// Leaves the main worktree out of the removal choices unless included.
+    #[test]
+    fn removable_worktrees_excludes_main_by_default() {
+        let worktrees = parse_worktree_porcelain(
+            "worktree /repo\nbranch refs/heads/main\n\nworktree /wt/feature\nbranch refs/heads/feature\n",
+        );
+        let paths = |include_main| -> Vec<PathBuf> {
+            removable_worktrees(&worktrees, include_main)
+                .iter()
+                .map(|wt| wt.path.clone())
+                .collect()
+        };
+
+        assert_eq!(paths(false), vec![PathBuf::from("/wt/feature")]);
+        assert_eq!(paths(true).len(), 2);
+        assert!(removable_worktrees(&worktrees[..1], false).is_empty());
+    }
+
+    /// Queries only the branches of attached worktrees.
+    #[test]
+    fn worktree_refs_skips_detached_worktrees() {
+        let worktrees = parse_worktree_porcelain(
+            "worktree /repo\nbranch refs/heads/main\n\nworktree /wt/abc1234\nHEAD abc1234\ndetached\n\nworktree /wt/feat/x\nbranch refs/heads/feat/x\n",
+        );
+
+        assert_eq!(
+            worktree_refs(&worktrees),
+            vec!["refs/heads/main", "refs/heads/feat/x"]
+        );
+    }
+
+    /// Keeps the first occurrence of each branch and ignores blanks and comments.
+    #[test]
+    fn parse_branch_list_skips_blanks_comments_and_repeats() {
+        let input = "main\n\n  develop  \n# bootstrap\nstaging\nmain\n";
+
+        assert_eq!(parse_branch_list(input), vec!["main", "develop", "staging"]);
+    }
+
+    /// Accepts exactly one non-blank line as the piped base ref.
+    #[test]
+    fn parse_stdin_ref_requires_a_single_line() {
+        assert_eq!(
+            parse_stdin_ref("\n  origin/main  \n\n").expect("one ref"),
+            "origin/main"
+        );
+        assert!(parse_stdin_ref(" \n").is_err());
+        assert!(parse_stdin_ref("main\ndevelop\n").is_err());
+    }
+
+    /// Turns every slash into a dash for flat worktree directories.
+    #[test]
+    fn flatten_branch_dir_replaces_slashes() {
+        assert_eq!(
+            flatten_branch_dir("feature/auth/login"),
+            "feature-auth-login"
+        );
+        assert_eq!(flatten_branch_dir("main"), "main");
+    }
+
+    /// Counts index and worktree changes separately and untracked files on their own.
+    #[test]
+    fn parse_status_counts_tallies_each_column() {
+        let output = "M  staged.rs\n M edited.rs\nMM both.rs\n?? new.rs\n?? other.rs\n";
+
+        assert_eq!(
+            parse_status_counts(output),
+            StatusCounts {
+                staged: 2,
+                unstaged: 2,
+                untracked: 2,
+            }
+        );
+        assert_eq!(parse_status_counts(""), StatusCounts::default());
+    }
+
+    /// Finds the other worktree in both wordings of git's "already checked out" error.
+    #[test]
+    fn checked_out_path_reads_both_git_wordings() {
+        assert_eq!(
+            checked_out_path(
+                "Preparing worktree\nfatal: 'feature' is already checked out at '/wt/feature'"
+            ),
+            Some(PathBuf::from("/wt/feature"))
+        );
+        assert_eq!(
+            checked_out_path("fatal: 'main' is already used by worktree at '/repo'"),
+            Some(PathBuf::from("/repo"))
+        );
+        assert_eq!(checked_out_path("fatal: '/wt/x' already exists"), None);
+    }
+
+    /// Prefixes new branch names once and leaves already-prefixed ones alone.
+    #[test]
+    fn with_branch_prefix_skips_prefixed_names() {
+        assert_eq!(with_branch_prefix("login", Some("kev/")), "kev/login");
+        assert_eq!(with_branch_prefix("kev/login", Some("kev/")), "kev/login");
+        assert_eq!(with_branch_prefix("login", Some("")), "login");
+        assert_eq!(with_branch_prefix("login", None), "login");
+    }
+
+    /// Gives a flattened branch that clashes with an existing worktree the next free suffix.
+    #[test]
+    fn suffixed_worktree_path_skips_taken_directories() {
+        let root = Path::new("/wt/repo");
+        let flat = root.join(flatten_branch_dir("feat/x"));
+        let taken = [root.join("feat-x"), root.join("feat-x-2")];
+
+        assert_eq!(flat, root.join("feat-x"));
+        assert_eq!(
+            suffixed_worktree_path(&flat, |candidate| taken.iter().any(|t| t == candidate))
+                .expect("free suffix"),
+            root.join("feat-x-3")
+        );
+        assert_eq!(
+            suffixed_worktree_path(&root.join("feat/x"), |_| false).expect("free suffix"),
+            root.join("feat/x-2")
+        );
+        assert!(suffixed_worktree_path(&flat, |_| true).is_err());
+    }
+
+    /// Treats a branch differing only in case from a worktree as a collision.
+    #[test]
+    fn case_only_differences_get_a_suffix() {
+        let root = Path::new("/wt/repo");
+        let worktrees = [WorktreeInfo {
+            path: root.join("Feature"),
+            branch: Some("Feature".to_string()),
+            ..WorktreeInfo::default()
+        }];
+        let taken = |candidate: &Path| worktree_path_registered(&worktrees, candidate);
+        let path = root.join("feature");
+
+        assert!(taken(&path));
+        assert!(taken(&root.join("Feature")));
+        assert!(!taken(&root.join("feature-x")));
+        assert_eq!(
+            suffixed_worktree_path(&path, taken).expect("free suffix"),
+            root.join("feature-2")
+        );
+    }
+
+    /// Confirms worktree lookup returns the expected entry.
+    #[test]
+    fn worktree_for_branch_finds_matching_entry() {
+        let worktrees = vec![
+            WorktreeInfo {
+                path: PathBuf::from("/tmp/one"),
+                branch: Some("main".to_string()),
+                head: None,
+                is_main: false,
+                ..WorktreeInfo::default()
+            },
+            WorktreeInfo {
+                path: PathBuf::from("/tmp/two"),
+                branch: Some("feature".to_string()),
+                head: None,
+                is_main: false,
+                ..WorktreeInfo::default()
+            },
+        ];
+
+        let found = worktree_for_branch(&worktrees, "feature").expect("missing worktree");
+
+        assert_eq!(found.path, PathBuf::from("/tmp/two"));
+    }
+
+    /// Returns the first listed entry when a branch is checked out twice.
+    #[test]
+    fn worktree_for_branch_prefers_first_listed_duplicate() {
+        let worktrees: Vec<WorktreeInfo> = ["/tmp/first", "/tmp/second"]
+            .iter()
+            .map(|path| WorktreeInfo {
+                path: PathBuf::from(path),
+                branch: Some("feature".to_string()),
+                head: None,
+                is_main: false,
+                ..WorktreeInfo::default()
+            })
+            .collect();
+
+        let found = worktree_for_branch(&worktrees, "feature").expect("missing worktree");
+
+        assert_eq!(found.path, PathBuf::from("/tmp/first"));
+    }
+
+    /// Puts the most recently visited duplicate first and keeps git order otherwise.
+    #[test]
+    fn order_by_last_visit_prefers_recent_paths() {
+        let worktrees: Vec<WorktreeInfo> = ["/tmp/a", "/tmp/b", "/tmp/c"]
+            .iter()
+            .map(|path| WorktreeInfo {
+                path: PathBuf::from(path),
+                branch: Some("feature".to_string()),
+                head: None,
+                is_main: false,
+                ..WorktreeInfo::default()
+            })
+            .collect();
+        let visit = |path: &str, visited_unix| state::Visit {
+            branch: "feature".to_string(),
+            path: PathBuf::from(path),
+            visited_unix,
+        };
+        let visits = vec![visit("/tmp/b", 5), visit("/tmp/c", 9), visit("/tmp/b", 1)];
+        let mut ordered: Vec<&WorktreeInfo> = worktrees.iter().collect();
+
+        order_by_last_visit(&mut ordered, &visits);
+
+        let paths: Vec<&Path> = ordered.iter().map(|wt| wt.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("/tmp/c"),
+                Path::new("/tmp/b"),
+                Path::new("/tmp/a")
+            ]
+        );
+    }
+
+    /// Treats blank input as absent and trims surrounding whitespace.
+    #[test]
+    fn non_empty_trimmed_drops_blank_values() {
+        assert_eq!(non_empty_trimmed("  "), None);
+        assert_eq!(non_empty_trimmed(" main "), Some("main".to_string()));
+    }
+
+    /// Escapes regex and quote characters in custom cd prefixes.
+    #[test]
+    fn grep_prefix_pattern_escapes_special_characters() {
+        assert_eq!(grep_prefix_pattern("GWW_CD:"), "'^GWW_CD:'");
+        assert_eq!(grep_prefix_pattern("cd.*$"), "'^cd\\.\\*\\$'");
+        assert_eq!(grep_prefix_pattern("it's:"), "'^it'\\''s:'");
+    }
+
+    /// Strips exactly the configured prefix from marker lines.
+    #[test]
+    fn autocd_script_uses_custom_prefix() {
+        let script = autocd_script(">>cd ");
+
+        assert!(script.contains("grep '^>>cd ' | cut -c6-"));
+        assert_eq!(
+            script
+                .matches("GWW_SHELL_INTEGRATION=1 GWW_CD_PREFIX='>>cd ' command gww")
+                .count(),
+            2
+        );
+        assert!(script.contains("grep '^GWW_ERR:' | cut -c9-"));
+        assert!(!script.contains(CD_PREFIX));
+    }
+
+    /// Uses printf instead of echo and ends cd options before the path.
+    #[test]
+    fn autocd_script_quotes_output_and_paths() {
+        let script = autocd_script(CD_PREFIX);
+
+        assert!(!script.contains("echo "));
+        assert!(script.contains("printf '%s\\n' \"$output\" | grep -v '^GWW_ERR:'"));
+        assert!(script.contains("printf '%s\\n' \"$1\" | grep '^GWW_ERR:'"));
+        assert_eq!(script.matches("cd -- \"$cd_path\"").count(), 2);
+    }
+
+    /// Keeps detached worktrees and records their HEAD commit.
+    #[test]
+    fn parse_worktree_porcelain_includes_detached_entries() {
+        let output = "worktree /repo\nHEAD 1111111111111111111111111111111111111111\nbranch refs/heads/main\n\nworktree /wt/scratch\nHEAD abcdef0123456789abcdef0123456789abcdef01\ndetached\n";
+
+        let worktrees = parse_worktree_porcelain(output);
+
+        assert_eq!(worktrees.len(), 2);
+        assert_eq!(worktrees[0].branch.as_deref(), Some("main"));
+        assert!(worktrees[0].is_main);
+        assert!(!worktrees[1].is_main);
+        assert!(!is_detached(&worktrees[0]));
+        assert!(is_detached(&worktrees[1]));
+        assert_eq!(
+            worktree_label(&worktrees[1]),
+            "(detached abcdef0) /wt/scratch"
+        );
+    }
+
+    /// Reads bare, locked, and prunable attributes, with and without reasons.
+    #[test]
+    fn parse_worktree_porcelain_reads_all_attributes() {
+        let output = "worktree /srv/repo.git\nbare\n\nworktree /wt/locked\nHEAD 2222222222222222222222222222222222222222\nbranch refs/heads/feature\nlocked on a USB drive\n\nworktree /wt/plain-lock\nHEAD 3333333333333333333333333333333333333333\ndetached\nlocked\n\nworktree /wt/gone\nHEAD 4444444444444444444444444444444444444444\nbranch refs/heads/gone\nprunable gitdir file points to non-existent location\nfuture-attribute x\n";
+
+        let worktrees = parse_worktree_porcelain(output);
+
+        assert_eq!(worktrees.len(), 4);
+        assert!(worktrees[0].bare && worktrees[0].is_main);
+        assert_eq!(worktrees[0].head, None);
+        assert_eq!(worktrees[1].locked.as_deref(), Some("on a USB drive"));
+        assert!(!worktrees[1].detached);
+        assert!(worktrees[2].detached);
+        assert_eq!(worktrees[2].locked.as_deref(), Some(""));
+        assert_eq!(worktrees[2].branch, None);
+        assert_eq!(worktrees[3].branch.as_deref(), Some("gone"));
+        assert_eq!(
+            worktrees[3].prunable.as_deref(),
+            Some("gitdir file points to non-existent location")
+        );
+        assert_eq!(worktrees[3].locked, None);
+    }
+
+    /// Parses plain and vendor-suffixed git version strings.
+    #[test]
+    fn parse_git_version_handles_vendor_suffixes() {
+        assert_eq!(
+            parse_git_version("git version 2.43.0\n"),
+            Some(GitVersion(2, 43, 0))
+        );
+        assert_eq!(
+            parse_git_version("git version 2.39.3 (Apple Git-146)"),
+            Some(GitVersion(2, 39, 3))
+        );
+        assert_eq!(
+            parse_git_version("git version 2.45.1.windows.1"),
+            Some(GitVersion(2, 45, 1))
+        );
+        assert_eq!(parse_git_version("not git"), None);
+    }
+
+    /// Picks the latest visit elsewhere, skipping repeats of the current worktree.
+    #[test]
+    fn previous_visit_skips_current_worktree() {
+        let visit = |path: &str, visited_unix| state::Visit {
+            branch: String::new(),
+            path: PathBuf::from(path),
+            visited_unix,
+        };
+        let visits = vec![
+            visit("/wt/a", 1),
+            visit("/wt/b", 2),
+            visit("/wt/c", 3),
+            visit("/wt/c", 4),
+        ];
+
+        assert_eq!(
+            previous_visit(&visits).map(|visit| visit.path.as_path()),
+            Some(Path::new("/wt/b"))
+        );
+        assert_eq!(previous_visit(&visits[..1]), None);
+        assert_eq!(previous_visit(&[]), None);
+    }
+
+    /// Replaces a missing-executable error with installation advice.
+    #[test]
+    fn git_spawn_error_explains_missing_git() {
+        let missing = git_spawn_error(std::io::Error::from(std::io::ErrorKind::NotFound));
+        let denied = git_spawn_error(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+
+        assert!(missing.to_string().starts_with("git executable not found"));
+        assert!(!denied.to_string().contains("not found"));
+    }
+
+    /// Joins relative paths to the base and resolves existing ones.
+    #[test]
+    fn absolute_cd_path_resolves_relative_paths() {
+        let base = unique_temp_dir("cd-path");
+        fs::create_dir_all(base.join("wt")).expect("create worktree dir");
+        let canonical_base = fs::canonicalize(&base).expect("canonical base");
+
+        assert_eq!(
+            absolute_cd_path(&base, Path::new("wt/../wt")),
+            canonical_base.join("wt")
+        );
+        assert_eq!(
+            absolute_cd_path(&base, Path::new("missing")),
+            base.join("missing")
+        );
+        assert_eq!(
+            absolute_cd_path(&base, Path::new("/nonexistent/gww")),
+            PathBuf::from("/nonexistent/gww")
+        );
+        fs::remove_dir_all(&base).expect("cleanup");
+    }
+
+    /// Compares paths literally when they cannot be resolved.
+    #[test]
+    fn same_path_falls_back_to_literal_comparison() {
+        let missing = Path::new("/nonexistent/gww/one");
+
+        assert!(same_path(missing, Path::new("/nonexistent/gww/one")));
+        assert!(!same_path(missing, Path::new("/nonexistent/gww/two")));
+    }
+
+    /// Splits literals from placeholders and turns doubled braces into literal ones.
+    #[test]
+    fn template_parts_reads_placeholders_and_escapes() {
+        use TemplatePart::{Literal, Placeholder};
+
+        assert_eq!(
+            template_parts("a{b}c{{d}}").expect("valid"),
+            vec![
+                Literal("a"),
+                Placeholder("b"),
+                Literal("c"),
+                Literal("{"),
+                Literal("d"),
+                Literal("}"),
+            ]
+        );
+        assert!(template_parts("x{y").is_err());
+        assert_eq!(
+            unknown_placeholder("x", "scratch_name", &["date", "time"]).to_string(),
+            "Unknown placeholder {x} in scratch_name; use {date} or {time}"
+        );
+    }
+
+    /// Parses placeholders between literals and rejects unknown or unclosed ones.
+    #[test]
+    fn parse_list_format_splits_fields_and_literals() {
+        assert_eq!(
+            parse_list_format("{branch}\t{path} ({ahead})").expect("valid template"),
+            vec![
+                FormatPart::Field(ListField::Branch),
+                FormatPart::Literal("\t".to_string()),
+                FormatPart::Field(ListField::Path),
+                FormatPart::Literal(" (".to_string()),
+                FormatPart::Field(ListField::Ahead),
+                FormatPart::Literal(")".to_string()),
+            ]
+        );
+        assert!(parse_list_format("{branch} {sha}").is_err());
+        assert!(parse_list_format("{branch").is_err());
+    }
+
+    /// Fills fields from metadata and leaves missing values empty.
+    #[test]
+    fn render_list_format_fills_fields() {
+        let parts = parse_list_format("{branch}|{path}|{subject}|{author}|{time}|{ahead}|{behind}")
+            .expect("valid template");
+        let worktree = WorktreeInfo {
+            path: PathBuf::from("/wt/feature"),
+            branch: Some("feature".to_string()),
+            head: None,
+            is_main: false,
+            ..WorktreeInfo::default()
+        };
+        let meta = BranchMeta {
+            timestamp_unix: 1,
+            summary: BranchSummary {
+                timestamp_label: "2024-06-01T10:00:00+00:00".to_string(),
+                author: "Ada".to_string(),
+                subject: "Add parser".to_string(),
+                track: Some(UpstreamTrack::Counts {
+                    ahead: 2,
+                    behind: 0,
+                }),
+            },
+        };
+        let detached = WorktreeInfo {
+            path: PathBuf::from("/wt/detached"),
+            branch: None,
+            head: Some("abc".to_string()),
+            detached: true,
+            ..WorktreeInfo::default()
+        };
+
+        assert_eq!(
+            render_list_format(&parts, &worktree, Some(&meta)),
+            "feature|/wt/feature|Add parser|Ada|2024-06-01T10:00:00+00:00|2|0"
+        );
+        let in_sync = parse_branch_metadata(
+            "feature\t1\t2024-06-01T10:00:00+00:00\tAda\trefs/remotes/origin/feature\t\tAdd parser\n",
+        );
+        assert_eq!(
+            render_list_format(&parts, &worktree, in_sync.get("feature")),
+            "feature|/wt/feature|Add parser|Ada|2024-06-01T10:00:00+00:00|0|0"
+        );
+        assert_eq!(
+            render_list_format(&parts, &detached, None),
+            "|/wt/detached|||||"
+        );
+    }
+
+    /// Tells empty, occupied, and already checked-out directories apart.
+    #[test]
+    fn existing_path_classifies_pre_existing_directories() {
+        let root = unique_temp_dir("existing");
+        let empty = root.join("empty");
+        let occupied = root.join("occupied");
+        let clone = root.join("clone");
+        fs::create_dir_all(&empty).expect("create empty dir");
+        fs::create_dir_all(&occupied).expect("create occupied dir");
+        fs::create_dir_all(clone.join(".git")).expect("create clone dir");
+        fs::write(occupied.join("notes.txt"), "draft").expect("write file");
+
+        assert_eq!(existing_path(&root.join("missing")), ExistingPath::Missing);
+        assert_eq!(existing_path(&empty), ExistingPath::EmptyDir);
+        assert_eq!(existing_path(&occupied), ExistingPath::Occupied);
+        assert_eq!(existing_path(&clone), ExistingPath::Checkout);
+        assert_eq!(
+            existing_path(&occupied.join("notes.txt")),
+            ExistingPath::File
+        );
+        fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    /// Renders scratch names from the local time in git's identity and HEAD's subject.
+    #[test]
+    fn render_scratch_name_uses_local_time_and_subject() {
+        let local =
+            parse_ident_local_time("Ada <ada@example.com> 1717254000 +0200").expect("valid ident");
+
+        assert_eq!(
+            render_scratch_name(DEFAULT_SCRATCH_NAME, local, "").expect("valid"),
+            "scratch/2024-06-01-1700"
+        );
+        assert_eq!(
+            render_scratch_name("try/{subject}", local, "Fix: parser's edge-cases!")
+                .expect("valid"),
+            "try/fix-parser-s-edge-cases"
+        );
+        assert_eq!(
+            render_scratch_name("try/{subject}", local, "???").expect("valid"),
+            "try/untitled"
+        );
+        assert!(render_scratch_name("try/{user}", local, "").is_err());
+        assert_eq!(parse_ident_local_time("Ada <ada@example.com>"), None);
+        assert_eq!(civil_from_unix(951_782_400), (2000, 2, 29, 0, 0));
+    }
+
+    /// Pads shorter source tags to the widest label.
+    #[test]
+    fn tag_padding_aligns_to_widest_label() {
+        assert_eq!(tag_padding("[T*]", 1), "");
+        assert_eq!(tag_padding("[wt ]", 3), " ");
+        assert_eq!(tag_padding("[rem*]", 3), "");
+    }
+
+    /// Buckets commit ages by the day thresholds, with each upper bound exclusive.
+    #[test]
+    fn age_bucket_uses_day_thresholds() {
+        let day = 24 * 60 * 60;
+
+        assert_eq!(age_bucket(3600, [1, 7, 30]), AgeBucket::Today);
+        assert_eq!(age_bucket(day, [1, 7, 30]), AgeBucket::Week);
+        assert_eq!(age_bucket(10 * day, [1, 7, 30]), AgeBucket::Month);
+        assert_eq!(age_bucket(30 * day, [1, 7, 30]), AgeBucket::Older);
+        assert_eq!(age_bucket(10 * day, [2, 14, 60]), AgeBucket::Week);
+    }
+
+    /// Flags computed paths under a worktree, including ones not created yet.
+    #[test]
+    fn enclosing_worktree_detects_root_inside_repo() {
+        let base = unique_temp_dir("enclosing");
+        let repo = base.join("repo");
+        fs::create_dir_all(&repo).expect("create repo dir");
+        let worktrees = vec![WorktreeInfo {
+            path: repo.clone(),
+            branch: Some("main".to_string()),
+            head: None,
+            is_main: false,
+            ..WorktreeInfo::default()
+        }];
+        let inside = repo.join("worktrees").join("repo").join("feature");
+        let outside = base.join("worktrees").join("repo").join("feature");
+
+        assert_eq!(
+            enclosing_worktree(&inside, &worktrees).map(|wt| wt.path.clone()),
+            Some(repo.clone())
+        );
+        assert!(enclosing_worktree(&outside, &worktrees).is_none());
+        assert!(enclosing_worktree(&base.join("repo-other"), &worktrees).is_none());
+        fs::remove_dir_all(&base).expect("cleanup");
+    }
+
+    /// Matches wildcard and single-character glob patterns.
+    #[test]
+    fn glob_match_supports_star_and_question_mark() {
+        assert!(glob_match("dependabot/*", "dependabot/npm/lodash-4.17"));
+        assert!(glob_match("*/renovate/*", "origin/renovate/serde"));
+        assert!(glob_match("fix-?", "fix-1"));
+        assert!(glob_match("main", "main"));
+        assert!(!glob_match("fix-?", "fix-10"));
+        assert!(!glob_match("dependabot/*", "feature/dependabot"));
+    }
+
+    /// Filters ignored local and remote branches but keeps worktrees.
+    #[test]
+    fn assemble_branch_candidates_skips_ignored_non_worktree_branches() {
+        let worktrees = vec![WorktreeInfo {
+            path: PathBuf::from("/tmp/bot"),
+            branch: Some("dependabot/kept".to_string()),
+            head: None,
+            is_main: false,
+            ..WorktreeInfo::default()
+        }];
+        let locals = vec!["dependabot/local".to_string(), "feature".to_string()];
+        let remotes = vec!["origin/dependabot/remote".to_string()];
+        let filter = CandidateOptions {
+            ignore: vec!["dependabot/*".to_string()],
+            ..CandidateOptions::default()
+        };
+
+        let candidates = assemble_branch_candidates(
+            &worktrees,
+            &locals,
+            &remotes,
+            &filter,
+            &HashMap::new(),
+            None,
+        );
+        let names: Vec<&str> = candidates.iter().map(|c| c.name.as_str()).collect();
+
+        assert_eq!(names, vec!["dependabot/kept", "feature"]);
+    }
+
+    /// Leaves out worktree branches and the remotes they shadow.
+    #[test]
+    fn available_sources_exclude_worktree_branches() {
+        let worktrees = vec![WorktreeInfo {
+            path: PathBuf::from("/tmp/feature"),
+            branch: Some("feature".to_string()),
+            head: None,
+            is_main: false,
+            ..WorktreeInfo::default()
+        }];
+        let locals = vec!["feature".to_string(), "idle".to_string()];
+        let remotes = vec!["origin/feature".to_string(), "origin/fresh".to_string()];
+
+        let candidates = assemble_branch_candidates(
+            &worktrees,
+            &locals,
+            &remotes,
+            &CandidateOptions::default(),
+            &HashMap::new(),
+            None,
+        );
+        let names: Vec<String> = filter_by_source(candidates, &AVAILABLE_SOURCES)
+            .into_iter()
+            .map(|candidate| candidate.name)
+            .collect();
+
+        assert_eq!(names, vec!["idle", "origin/fresh"]);
+    }
+
+    /// Filters candidates by source and keeps everything without a filter.
+    #[test]
+    fn filter_by_source_keeps_requested_sources() {
+        let worktrees = vec![WorktreeInfo {
+            path: PathBuf::from("/tmp/feature"),
+            branch: Some("feature".to_string()),
+            head: None,
+            is_main: false,
+            ..WorktreeInfo::default()
+        }];
+        let locals = vec!["idle".to_string()];
+        let remotes = vec!["origin/fresh".to_string()];
+        let candidates = assemble_branch_candidates(
+            &worktrees,
+            &locals,
+            &remotes,
+            &CandidateOptions::default(),
+            &HashMap::new(),
+            None,
+        );
+        let names = |sources: &[BranchSource]| -> Vec<String> {
+            filter_by_source(candidates.clone(), sources)
+                .into_iter()
+                .map(|candidate| candidate.name)
+                .collect()
+        };
+
+        assert_eq!(names(&[]), vec!["feature", "idle", "origin/fresh"]);
+        assert_eq!(
+            names(&[BranchSource::Remote, BranchSource::Worktree]),
+            vec!["feature", "origin/fresh"]
+        );
+    }
+
+    /// Applies bare sort keys to every group and `SOURCE=KEY` to one, later values winning.
+    #[test]
+    fn resolve_source_sorts_layers_config_and_flags() {
+        let configured = config::SortOrder {
+            remote: Some(config::SortKey::Name),
+            ..config::SortOrder::default()
+        };
+        let specs: Vec<SortSpec> = ["accessed", "local=commit"]
+            .iter()
+            .map(|value| parse_sort_spec(value).expect("valid spec"))
+            .collect();
+
+        assert_eq!(
+            resolve_source_sorts(&[], &configured),
+            SourceSorts {
+                worktree: BranchSort::Commit,
+                local: BranchSort::Commit,
+                remote: BranchSort::Name,
+            }
+        );
+        assert_eq!(
+            resolve_source_sorts(&specs, &configured),
+            SourceSorts {
+                worktree: BranchSort::Accessed,
+                local: BranchSort::Commit,
+                remote: BranchSort::Accessed,
+            }
+        );
+        assert!(parse_sort_spec("tags=name").is_err());
+        assert!(parse_sort_spec("remote=size").is_err());
     }
-    let root = git_output(["rev-parse", "--show-toplevel"])?;
-    let path = Path::new(root.trim());
-    path.file_name()
-        .and_then(|name| name.to_str())
-        .map(|name| name.to_string())
-        .context("Unable to determine repository name")
-}
 
-/// Extracts the repository name from a git remote URL.
-fn repo_name_from_url(url: &str) -> Option<String> {
-    let cleaned = url.trim_end_matches('/');
-    let name = cleaned.rsplit('/').next()?;
-    Some(name.trim_end_matches(".git").to_string())
-}
+    /// Puts visited branches first by visit time and keeps commit order for the rest.
+    #[test]
+    fn sort_by_access_falls_back_to_existing_order() {
+        let mut names: Vec<String> = ["new-commit", "visited-old", "old-commit", "visited-new"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        let access_times = HashMap::from([
+            ("visited-old".to_string(), 10),
+            ("visited-new".to_string(), 20),
+        ]);
 
-/// Runs `git worktree add` with optional branch creation.
-fn git_worktree_add(path: &Path, branch: Option<&str>, remote: Option<&str>) -> Result<()> {
-    let mut cmd = Command::new("git");
-    cmd.arg("worktree").arg("add").arg(path);
+        sort_by_access(&mut names, &access_times);
 
-    if let Some(remote_branch) = remote {
-        let local_branch = branch.context("local branch required for remote")?;
-        cmd.arg("-b").arg(local_branch).arg(remote_branch);
-    } else if let Some(branch) = branch {
-        if branch_exists(branch) {
-            cmd.arg(branch);
-        } else {
-            cmd.arg("-b").arg(branch);
-        }
+        assert_eq!(
+            names,
+            vec!["visited-new", "visited-old", "new-commit", "old-commit"]
+        );
     }
 
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)
-            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    /// Picks the newest matching branch, matching remotes without their prefix.
+    #[test]
+    fn pick_latest_branch_uses_newest_match() {
+        let meta = meta_with_timestamps(&[
+            ("release/1.0", 10),
+            ("origin/release/2.0", 30),
+            ("feature", 40),
+            ("release/1.1", 20),
+        ]);
+        let remotes = vec!["origin/release/2.0".to_string()];
+
+        assert_eq!(
+            pick_latest_branch(&meta, &remotes, "release/*").as_deref(),
+            Some("origin/release/2.0")
+        );
+        assert_eq!(
+            pick_latest_branch(&meta, &[], "release/*").as_deref(),
+            Some("release/1.1")
+        );
+        assert_eq!(pick_latest_branch(&meta, &remotes, "hotfix/*"), None);
     }
 
-    let status = cmd.status().context("Failed to run git worktree add")?;
-    if !status.success() {
-        anyhow::bail!("git worktree add failed");
+    /// Keeps remote branches shadowed by local ones only under `all_remotes`.
+    #[test]
+    fn assemble_branch_candidates_all_remotes_skips_dedup() {
+        let locals = vec!["x".to_string()];
+        let remotes = vec!["origin/x".to_string(), "upstream/x".to_string()];
+        let names = |options: &CandidateOptions| -> Vec<String> {
+            assemble_branch_candidates(&[], &locals, &remotes, options, &HashMap::new(), None)
+                .into_iter()
+                .map(|candidate| candidate.name)
+                .collect()
+        };
+        let all_remotes = CandidateOptions {
+            all_remotes: true,
+            ..CandidateOptions::default()
+        };
+
+        assert_eq!(names(&CandidateOptions::default()), vec!["x"]);
+        assert_eq!(names(&all_remotes), vec!["x", "origin/x", "upstream/x"]);
     }
-    if should_init_submodules_on_checkout() {
-        init_submodules(path)?;
+
+    /// Pins the default branch ahead of the current branch, then orders by recency.
+    #[test]
+    fn assemble_branch_candidates_applies_pinning_precedence() {
+        let worktrees: Vec<WorktreeInfo> = ["main", "current", "recent"]
+            .iter()
+            .map(|name| WorktreeInfo {
+                path: PathBuf::from(format!("/tmp/{name}")),
+                branch: Some(name.to_string()),
+                head: None,
+                is_main: false,
+                ..WorktreeInfo::default()
+            })
+            .collect();
+        let meta = meta_with_timestamps(&[("main", 10), ("current", 20), ("recent", 30)]);
+        let names = |options: &CandidateOptions| -> Vec<String> {
+            assemble_branch_candidates(&worktrees, &[], &[], options, &meta, Some("current"))
+                .into_iter()
+                .map(|candidate| candidate.name)
+                .collect()
+        };
+
+        let strict = CandidateOptions::default();
+        let current_first = CandidateOptions {
+            current_first: true,
+            ..CandidateOptions::default()
+        };
+        let both = CandidateOptions {
+            current_first: true,
+            pinned_main: Some("main".to_string()),
+            ..CandidateOptions::default()
+        };
+
+        assert_eq!(names(&strict), vec!["recent", "current", "main"]);
+        assert_eq!(names(&current_first), vec!["current", "recent", "main"]);
+        assert_eq!(names(&both), vec!["main", "current", "recent"]);
     }
-    Ok(())
-}
 
-/// Returns true when submodules should be initialized after checkout.
-fn should_init_submodules_on_checkout() -> bool {
-    env::var_os("GWW_SUBMODULE_ON_CHECKOUT").is_some()
-}
+    /// Substitutes placeholders and finds the directory above `{branch}`.
+    #[test]
+    fn layout_placeholders_and_repo_dir() {
+        let value = |name: &str| match name {
+            "root" => Ok("/wt".to_string()),
+            "owner" => Ok("org".to_string()),
+            "repo" => Ok("api".to_string()),
+            other => anyhow::bail!("unknown {other}"),
+        };
 
-/// Initializes submodules recursively for a worktree path.
-fn init_submodules(path: &Path) -> Result<()> {
-    let status = Command::new("git")
-        .arg("-C")
-        .arg(path)
-        .args(["submodule", "update", "--init", "--recursive"])
-        .status()
-        .context("Failed to run git submodule update")?;
-    if !status.success() {
-        anyhow::bail!("git submodule update failed");
+        assert_eq!(
+            fill_placeholders("{root}/{owner}/{repo}", value).unwrap(),
+            "/wt/org/api"
+        );
+        assert!(fill_placeholders("{root}/{nope}", value).is_err());
+        assert!(fill_placeholders("{root}/{repo", value).is_err());
+        assert_eq!(
+            fill_placeholders("{{root}}/{repo}}", value).unwrap(),
+            "{root}/api}"
+        );
+        assert_eq!(
+            layout_repo_dir("{root}/{owner}/{repo}/{branch}"),
+            "{root}/{owner}/{repo}"
+        );
+        assert_eq!(layout_repo_dir("{root}/{repo}-{branch}"), "{root}");
     }
-    Ok(())
-}
 
-/// Runs `git worktree remove` for the selected path.
-fn git_worktree_remove(path: &Path, force: bool) -> Result<()> {
-    if force {
-        let status = Command::new("git")
-            .args(["worktree", "remove", "--force"])
-            .arg(path)
-            .status()
-            .context("Failed to run git worktree remove --force")?;
-        if !status.success() {
-            anyhow::bail!("git worktree remove failed");
-        }
-        return Ok(());
+    /// Expands both variable forms before placeholders and rejects unset ones.
+    #[test]
+    fn expand_env_vars_in_layouts() {
+        let lookup = |name: &str| match name {
+            "HOME" => Some("/home/me".to_string()),
+            "WT_DIR" => Some("wt".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(
+            expand_env_vars("${HOME}/wt/{repo}/{branch}", lookup).unwrap(),
+            "/home/me/wt/{repo}/{branch}"
+        );
+        assert_eq!(
+            expand_env_vars("$HOME/$WT_DIR-1/{branch}", lookup).unwrap(),
+            "/home/me/wt-1/{branch}"
+        );
+        assert_eq!(
+            expand_env_vars("/cost$/{branch}", lookup).unwrap(),
+            "/cost$/{branch}"
+        );
+        assert!(expand_env_vars("${UNSET}/{branch}", lookup).is_err());
+        assert!(expand_env_vars("$UNSET/{branch}", lookup).is_err());
+        assert!(expand_env_vars("${HOME/{branch}", lookup).is_err());
+        assert!(expand_env_vars("${}/{branch}", lookup).is_err());
     }
 
-    let output = Command::new("git")
-        .args(["worktree", "remove"])
-        .arg(path)
-        .output()
-        .context("Failed to run git worktree remove")?;
-    if output.status.success() {
-        return Ok(());
+    /// Keeps braces from variable values literal instead of reading them as placeholders.
+    #[test]
+    fn expand_layout_template_escapes_variable_values() {
+        let lookup = |name: &str| match name {
+            "HOME" => Some("/home/{me}".to_string()),
+            "WT_DIR" => Some("wt}".to_string()),
+            _ => None,
+        };
+        let value = |name: &str| match name {
+            "branch" => Ok("feat".to_string()),
+            other => anyhow::bail!("unknown {other}"),
+        };
+
+        assert_eq!(
+            expand_layout_template("${HOME}/$WT_DIR/{branch}", lookup, value).unwrap(),
+            "/home/{me}/wt}/feat"
+        );
+        assert!(expand_layout_template("${HOME}/{nope}", lookup, value).is_err());
     }
 
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    let trimmed = stderr.trim();
-    let message = if trimmed.is_empty() {
-        "git worktree remove failed"
-    } else {
-        trimmed
-    };
+    /// Retries network hiccups but not authentication or unknown failures.
+    #[test]
+    fn is_transient_git_error_separates_network_from_auth() {
+        assert!(is_transient_git_error(
+            "fatal: unable to access 'https://example.com/r.git/': Could not resolve host: example.com\n"
+        ));
+        assert!(is_transient_git_error(
+            "error: RPC failed; curl 56 Recv failure: Connection reset by peer\nfatal: early EOF\n"
+        ));
+        assert!(!is_transient_git_error(
+            "remote: Invalid username or password.\nfatal: Authentication failed for 'https://example.com/r.git/'\n"
+        ));
+        assert!(!is_transient_git_error(
+            "git@example.com: Permission denied (publickey).\nfatal: Could not read from remote repository.\n\nfatal: the remote end hung up unexpectedly\n"
+        ));
+        assert!(!is_transient_git_error(
+            "fatal: couldn't find remote ref nope\n"
+        ));
+    }
 
-    let should_remove = Confirm::new()
-        .with_prompt(format!(
-            "{message}. Force remove anyway? This will delete the worktree from disk."
-        ))
-        .default(false)
-        .interact()?;
-    if !should_remove {
-        anyhow::bail!("Worktree removal cancelled");
+    /// Rejects empty and blank branch arguments and trims the rest.
+    #[test]
+    fn trimmed_branch_arg_rejects_blank_names() {
+        assert!(trimmed_branch_arg(Some(String::new())).is_err());
+        assert!(trimmed_branch_arg(Some("  ".to_string())).is_err());
+        assert_eq!(
+            trimmed_branch_arg(Some(" feature \n".to_string())).unwrap(),
+            Some("feature".to_string())
+        );
+        assert_eq!(trimmed_branch_arg(None).unwrap(), None);
     }
 
-    let status = Command::new("git")
-        .args(["worktree", "remove", "--force"])
-        .arg(path)
-        .status()
-        .context("Failed to run git worktree remove --force")?;
-    if !status.success() {
-        anyhow::bail!("git worktree remove failed");
+    /// Quotes only the words the shell would split or expand.
+    #[test]
+    fn shell_command_line_quotes_unsafe_words() {
+        let mut cmd = Command::new("git");
+        cmd.args(["worktree", "add", "/wt/my repo/it's", "-b", "feat/x", ""]);
+
+        assert_eq!(
+            shell_command_line(&cmd),
+            r#"git worktree add '/wt/my repo/it'\''s' -b feat/x ''"#
+        );
     }
-    Ok(())
-}
 
-/// Emits a tagged path for shell auto-cd scripts.
-fn emit_cd(path: &Path) {
-    println!("{CD_PREFIX}{}", path.display());
-}
+    /// Parses HTTPS, SSH, and scp-style remotes including nested groups.
+    #[test]
+    fn parse_remote_url_extracts_host_owner_and_name() {
+        let expected = Some(RemoteRepo {
+            host: "github.com".to_string(),
+            owner: "org".to_string(),
+            name: "project".to_string(),
+        });
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        assert_eq!(
+            parse_remote_url("https://github.com/org/project.git"),
+            expected
+        );
+        assert_eq!(parse_remote_url("git@github.com:org/project.git"), expected);
+        assert_eq!(
+            parse_remote_url("ssh://git@github.com:22/org/project/"),
+            expected
+        );
+        assert_eq!(
+            parse_remote_url("git@gitlab.com:group/sub/app.git").map(|r| r.owner),
+            Some("group/sub".to_string())
+        );
+        assert_eq!(parse_remote_url("/srv/git/project.git"), None);
+    }
 
-    /// Verifies the sort order and de-duplication behavior.
+    /// Builds GitHub compare and GitLab merge request URLs.
     #[test]
-    fn sort_by_recent_orders_by_timestamp_and_dedups() {
-        let mut meta = HashMap::new();
-        meta.insert(
-            "main".to_string(),
-            BranchMeta {
-                timestamp_unix: 200,
-                summary: placeholder_summary(),
-            },
+    fn forge_compare_url_supports_github_and_gitlab() {
+        let github = RemoteRepo {
+            host: "github.com".to_string(),
+            owner: "org".to_string(),
+            name: "project".to_string(),
+        };
+        let gitlab = RemoteRepo {
+            host: "gitlab.com".to_string(),
+            owner: "group/sub".to_string(),
+            name: "app".to_string(),
+        };
+
+        assert_eq!(
+            forge_compare_url(&github, "main", "feature/x#1"),
+            "https://github.com/org/project/compare/main...feature/x%231?expand=1"
         );
-        meta.insert(
-            "feature".to_string(),
-            BranchMeta {
-                timestamp_unix: 100,
-                summary: placeholder_summary(),
-            },
+        assert_eq!(
+            forge_compare_url(&gitlab, "main", "fix"),
+            "https://gitlab.com/group/sub/app/-/merge_requests/new?merge_request[source_branch]=fix&merge_request[target_branch]=main"
         );
-        meta.insert(
-            "hotfix".to_string(),
-            BranchMeta {
-                timestamp_unix: 200,
-                summary: placeholder_summary(),
-            },
+    }
+
+    /// Counts every worktree or all but the main one.
+    #[test]
+    fn worktree_count_optionally_excludes_main() {
+        let worktrees = parse_worktree_porcelain(
+            "worktree /repo\nbranch refs/heads/main\n\nworktree /wt/feature\nbranch refs/heads/feature\n",
         );
 
-        let ordered = sort_by_recent(["feature", "main", "feature", "hotfix"], &meta);
+        assert_eq!(worktree_count(&worktrees, false), 2);
+        assert_eq!(worktree_count(&worktrees, true), 1);
+        assert_eq!(worktree_count(&[], true), 0);
+    }
 
-        assert_eq!(ordered, vec!["hotfix", "main", "feature"]);
+    /// Applies since/until bounds and drops missing metadata under `since`.
+    #[test]
+    fn in_date_range_applies_bounds() {
+        let meta = meta_with_timestamps(&[("old", 100), ("mid", 200), ("new", 300)]);
+        let filter = CandidateOptions {
+            since_unix: Some(150),
+            until_unix: Some(250),
+            ..CandidateOptions::default()
+        };
+        let until_only = CandidateOptions {
+            until_unix: Some(250),
+            ..CandidateOptions::default()
+        };
+
+        assert!(!in_date_range("old", &meta, &filter));
+        assert!(in_date_range("mid", &meta, &filter));
+        assert!(!in_date_range("new", &meta, &filter));
+        assert!(!in_date_range("unknown", &meta, &filter));
+        assert!(in_date_range("unknown", &meta, &until_only));
+        assert!(in_date_range("new", &meta, &CandidateOptions::default()));
     }
 
-    /// Ensures remote prefixes are stripped correctly.
+    /// Fast-forwards only branches strictly behind; diverged ones are flagged, not moved.
     #[test]
-    fn strip_remote_prefix_handles_remote_and_local_names() {
-        assert_eq!(strip_remote_prefix("origin/main"), "main");
-        assert_eq!(strip_remote_prefix("main"), "main");
+    fn upstream_action_only_fast_forwards_when_strictly_behind() {
+        let counts = |ahead, behind| Some(UpstreamTrack::Counts { ahead, behind });
+
+        assert_eq!(
+            upstream_action(counts(0, 3)),
+            UpstreamAction::FastForward { behind: 3 }
+        );
+        assert_eq!(
+            upstream_action(counts(2, 1)),
+            UpstreamAction::Diverged {
+                ahead: 2,
+                behind: 1
+            }
+        );
+        assert_eq!(upstream_action(counts(2, 0)), UpstreamAction::None);
+        assert_eq!(
+            upstream_action(Some(UpstreamTrack::Gone)),
+            UpstreamAction::None
+        );
+        assert_eq!(upstream_action(None), UpstreamAction::None);
     }
 
-    /// Accepts a single external argument as a branch name.
+    /// Parses ahead/behind counts, gone upstreams, and untracked branches.
     #[test]
-    fn branch_from_external_args_accepts_branch() {
-        let args = vec!["main".to_string()];
+    fn parse_upstream_track_handles_all_states() {
+        assert_eq!(
+            parse_upstream_track("refs/remotes/origin/main", "[ahead 2, behind 1]"),
+            Some(UpstreamTrack::Counts {
+                ahead: 2,
+                behind: 1
+            })
+        );
+        assert_eq!(
+            parse_upstream_track("refs/remotes/origin/main", "[behind 3]"),
+            Some(UpstreamTrack::Counts {
+                ahead: 0,
+                behind: 3
+            })
+        );
+        assert_eq!(
+            parse_upstream_track("refs/remotes/origin/main", "[gone]"),
+            Some(UpstreamTrack::Gone)
+        );
+        assert_eq!(parse_upstream_track("", ""), None);
+        assert_eq!(
+            format_upstream_track(parse_upstream_track(
+                "refs/remotes/origin/main",
+                "[ahead 2, behind 1]"
+            ))
+            .as_deref(),
+            Some("↑2 ↓1")
+        );
+        assert_eq!(format_upstream_track(None), None);
+    }
 
-        assert_eq!(branch_from_external_args(&args), Some("main".to_string()));
+    /// Reports a tracked branch with an empty track as zero ahead and behind.
+    #[test]
+    fn parse_upstream_track_treats_empty_track_as_in_sync() {
+        assert_eq!(
+            parse_upstream_track("refs/remotes/origin/main", ""),
+            Some(UpstreamTrack::Counts {
+                ahead: 0,
+                behind: 0
+            })
+        );
+        assert_eq!(
+            format_upstream_track(parse_upstream_track("refs/remotes/origin/main", "")),
+            None
+        );
     }
 
-    /// Rejects flags or extra arguments as checkout branch names.
+    /// Reads the tracking column while keeping tabs inside subjects.
     #[test]
-    fn branch_from_external_args_rejects_flags_and_extra_args() {
-        let flags = vec!["-h".to_string()];
-        let extra = vec!["main".to_string(), "extra".to_string()];
+    fn parse_branch_metadata_reads_track_column() {
+        let output = "main\t100\t2024-01-01T00:00:00Z\tAda\trefs/remotes/origin/main\t[ahead 1]\tFix\tthings\nlocal\t50\t2023-01-01T00:00:00Z\tBob\t\t\tInit\n";
 
-        assert_eq!(branch_from_external_args(&flags), None);
-        assert_eq!(branch_from_external_args(&extra), None);
+        let meta = parse_branch_metadata(output);
+
+        assert_eq!(meta["main"].timestamp_unix, 100);
+        assert_eq!(meta["main"].summary.subject, "Fix\tthings");
+        assert_eq!(
+            meta["main"].summary.track,
+            Some(UpstreamTrack::Counts {
+                ahead: 1,
+                behind: 0
+            })
+        );
+        assert_eq!(meta["local"].summary.track, None);
     }
 
-    /// Checks matching behavior for remote branches.
+    /// Orders worktrees by commit time, branch, or path, with detached ones last.
     #[test]
-    fn match_remote_branch_prefers_exact_match() {
-        let remotes = vec!["origin/main".to_string(), "upstream/dev".to_string()];
+    fn worktree_order_sorts_and_puts_detached_last() {
+        let worktree = |path: &str, branch: Option<&str>| WorktreeInfo {
+            path: PathBuf::from(path),
+            branch: branch.map(str::to_string),
+            ..WorktreeInfo::default()
+        };
+        let worktrees = vec![
+            worktree("/wt/c", Some("old")),
+            worktree("/wt/a", None),
+            worktree("/wt/b", Some("new")),
+            worktree("/wt/d", Some("unknown")),
+        ];
+        let mut meta = HashMap::new();
+        for (branch, timestamp_unix) in [("old", 100), ("new", 200)] {
+            meta.insert(
+                branch.to_string(),
+                BranchMeta {
+                    timestamp_unix,
+                    summary: placeholder_summary(),
+                },
+            );
+        }
 
         assert_eq!(
-            match_remote_branch("origin/main", &remotes),
-            Some("origin/main".to_string())
+            worktree_order(&worktrees, ListSort::Recent, &meta),
+            vec![2, 0, 3, 1]
         );
         assert_eq!(
-            match_remote_branch("dev", &remotes),
-            Some("upstream/dev".to_string())
+            worktree_order(&worktrees, ListSort::Alpha, &meta),
+            vec![2, 0, 3, 1]
+        );
+        assert_eq!(
+            worktree_order(&worktrees, ListSort::Path, &meta),
+            vec![1, 2, 0, 3]
         );
-        assert_eq!(match_remote_branch("missing", &remotes), None);
     }
 
-    /// Confirms worktree lookup returns the expected entry.
+    /// Marks the main worktree and the lines whose worktree directory is absent.
     #[test]
-    fn worktree_for_branch_finds_matching_entry() {
+    fn annotate_worktree_list_marks_main_and_absent_directories() {
+        let existing = env::temp_dir();
         let worktrees = vec![
             WorktreeInfo {
-                path: PathBuf::from("/tmp/one"),
+                path: existing.clone(),
                 branch: Some("main".to_string()),
+                head: None,
+                is_main: true,
+                ..WorktreeInfo::default()
             },
             WorktreeInfo {
-                path: PathBuf::from("/tmp/two"),
+                path: PathBuf::from("/nonexistent/gww/gone"),
+                branch: Some("gone".to_string()),
+                head: None,
+                is_main: false,
+                ..WorktreeInfo::default()
+            },
+            WorktreeInfo {
+                path: PathBuf::from("/nonexistent/gww/pruned"),
+                branch: Some("pruned".to_string()),
+                prunable: Some("gitdir file points to non-existent location".to_string()),
+                ..WorktreeInfo::default()
+            },
+        ];
+        let raw = "/repo  abc1234 [main]\n/nonexistent/gww/gone  abc1234 [gone]\n/nonexistent/gww/pruned  abc1234 [pruned] prunable\n";
+
+        assert_eq!(
+            annotate_worktree_list(raw, &worktrees),
+            "/repo  abc1234 [main] (main)\n/nonexistent/gww/gone  abc1234 [gone] (missing)\n/nonexistent/gww/pruned  abc1234 [pruned] (prunable: gitdir file points to non-existent location)\n"
+        );
+        assert_eq!(annotate_worktree_list("one\n", &worktrees), "one\n");
+    }
+
+    /// Returns a fresh, empty temporary directory path for a test.
+    fn unique_temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("gww-test-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Matches worktrees listed under a symlinked root with visits and records using resolved paths.
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_root_still_matches_worktrees() {
+        let base = unique_temp_dir("symlinked-root");
+        let real = base.join("real");
+        fs::create_dir_all(real.join("repo").join("feature")).expect("create worktree dir");
+        let link = base.join("link");
+        std::os::unix::fs::symlink(&real, &link).expect("symlink root");
+        let listed = link.join("repo").join("feature");
+        let resolved = fs::canonicalize(&listed).expect("resolve worktree");
+        let worktrees = [
+            WorktreeInfo {
+                path: link.join("repo").join("other"),
                 branch: Some("feature".to_string()),
+                head: None,
+                is_main: false,
+                ..WorktreeInfo::default()
+            },
+            WorktreeInfo {
+                path: listed.clone(),
+                branch: Some("feature".to_string()),
+                head: None,
+                is_main: false,
+                ..WorktreeInfo::default()
             },
         ];
+        let visits = vec![state::Visit {
+            branch: "feature".to_string(),
+            path: resolved.clone(),
+            visited_unix: 5,
+        }];
+        let mut ordered: Vec<&WorktreeInfo> = worktrees.iter().collect();
 
-        let found = worktree_for_branch(&worktrees, "feature").expect("missing worktree");
+        order_by_last_visit(&mut ordered, &visits);
 
-        assert_eq!(found.path, PathBuf::from("/tmp/two"));
+        assert_eq!(ordered[0].path, listed);
+        assert!(same_path(&listed, &resolved));
+        assert_eq!(
+            absolute_cd_path(Path::new("/"), &listed),
+            canonical_path(&resolved)
+        );
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    /// Removes parent directories it created when the worktree add fails.
+    #[test]
+    fn with_created_dirs_removes_created_parents_on_failure() {
+        let root = unique_temp_dir("add-failure");
+        fs::create_dir_all(&root).expect("create root");
+        let parent = root.join("repo").join("feature");
+
+        let result = with_created_dirs(&parent, || anyhow::bail!("hook declined"));
+
+        assert!(result.is_err());
+        assert!(!root.join("repo").exists());
+        assert!(root.exists());
+        fs::remove_dir_all(&root).expect("clean up");
+    }
+
+    /// Copies nested template files and links without clobbering checkout files.
+    #[test]
+    fn copy_template_keeps_existing_files_unless_overwriting() {
+        let root = unique_temp_dir("template");
+        let template = root.join("template");
+        let worktree = root.join("worktree");
+        fs::create_dir_all(template.join(".notes")).expect("create template");
+        fs::create_dir_all(&worktree).expect("create worktree");
+        fs::write(template.join(".notes").join("todo.md"), "notes").expect("write note");
+        fs::write(template.join("README.md"), "template").expect("write readme");
+        fs::write(worktree.join("README.md"), "checkout").expect("write checkout file");
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("/tmp/cache", template.join(".cache")).expect("link");
+
+        copy_template(&template, &worktree, false).expect("copy template");
+
+        let read = |path: PathBuf| fs::read_to_string(path).expect("read");
+        assert_eq!(read(worktree.join(".notes").join("todo.md")), "notes");
+        assert_eq!(read(worktree.join("README.md")), "checkout");
+        #[cfg(unix)]
+        assert_eq!(
+            fs::read_link(worktree.join(".cache")).expect("read link"),
+            PathBuf::from("/tmp/cache")
+        );
+
+        copy_template(&template, &worktree, true).expect("overwrite template");
+
+        assert_eq!(read(worktree.join("README.md")), "template");
+        fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    /// Stops removing at the first directory that still has content.
+    #[test]
+    fn remove_empty_dirs_stops_at_non_empty_directory() {
+        let root = unique_temp_dir("remove-empty");
+        let inner = root.join("a").join("b");
+        fs::create_dir_all(&inner).expect("create dirs");
+        fs::write(root.join("a").join("keep"), "").expect("write file");
+
+        remove_empty_dirs(&[inner.clone(), root.join("a"), root.clone()]);
+
+        assert!(!inner.exists());
+        assert!(root.join("a").exists());
+        fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    /// Keeps pre-existing and non-empty directories after a failure.
+    #[test]
+    fn with_created_dirs_keeps_existing_content() {
+        let root = unique_temp_dir("add-existing");
+        let repo = root.join("repo");
+        fs::create_dir_all(&repo).expect("create repo dir");
+        fs::write(repo.join("keep"), "").expect("write marker");
+
+        let result = with_created_dirs(&repo.join("feature"), || anyhow::bail!("failed"));
+
+        assert!(result.is_err());
+        assert!(repo.join("keep").exists());
+        assert!(!repo.join("feature").exists());
+
+        let ok = with_created_dirs(&repo.join("other"), || Ok(()));
+        assert!(ok.is_ok());
+        assert!(repo.join("other").exists());
+        fs::remove_dir_all(&root).expect("clean up");
     }
 
     /// Verifies repository names are extracted cleanly.
@@ -0,0 +1,75 @@
+use console::{Term, style};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const SHOW_AFTER: Duration = Duration::from_millis(200);
+const FRAME_INTERVAL: Duration = Duration::from_millis(80);
+
+/// Stderr spinner for slow work, cleared when dropped.
+///
+/// Nothing is drawn unless the work outlasts [`SHOW_AFTER`], and stderr must be
+/// a terminal. Stdout is not checked: the autocd wrapper always captures it,
+/// and the spinner never writes there.
+pub struct Spinner {
+    done: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    /// Starts a spinner showing `message` once the delay has passed.
+    pub fn start(message: &str) -> Spinner {
+        let term = Term::stderr();
+        if !term.is_term() {
+            return Spinner {
+                done: None,
+                handle: None,
+            };
+        }
+        let (done, stopped) = mpsc::channel::<()>();
+        let message = message.to_string();
+        let handle = thread::spawn(move || {
+            if stopped.recv_timeout(SHOW_AFTER) != Err(RecvTimeoutError::Timeout) {
+                return;
+            }
+            for frame in FRAMES.iter().cycle() {
+                let _ = term.write_str(&format!("\r{} {message}", style(frame).cyan()));
+                if stopped.recv_timeout(FRAME_INTERVAL) != Err(RecvTimeoutError::Timeout) {
+                    break;
+                }
+            }
+            let _ = term.clear_line();
+        });
+        Spinner {
+            done: Some(done),
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        // Dropping the sender disconnects the channel and wakes the thread.
+        self.done.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Returns without waiting for the display delay when work finishes quickly.
+    #[test]
+    fn drop_before_delay_returns_promptly() {
+        let start = Instant::now();
+
+        drop(Spinner::start("Loading branches..."));
+
+        assert!(start.elapsed() < SHOW_AFTER);
+    }
+}
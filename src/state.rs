@@ -0,0 +1,343 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{git_output, working_dir};
+
+const BRANCH_BASES_FILE: &str = "branch-bases";
+const WORKTREE_DIRS_FILE: &str = "worktree-dirs";
+const HISTORY_FILE: &str = "history";
+const LAST_REMOVED_FILE: &str = "last-removed";
+const BRANCH_PARENTS_FILE: &str = "branch-parents";
+const HISTORY_LIMIT: usize = 500;
+
+/// Creation metadata recorded for a branch gww created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchBase {
+    pub branch: String,
+    pub base: String,
+    pub created_unix: i64,
+}
+
+/// A worktree gww sent the shell to, oldest entries first in the history file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Visit {
+    /// Branch checked out in the worktree; empty for detached worktrees.
+    pub branch: String,
+    pub path: PathBuf,
+    pub visited_unix: i64,
+}
+
+/// A stacked branch and the parent branch it was created on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchParent {
+    pub branch: String,
+    pub parent: String,
+    /// Parent commit the branch started from, for rebasing onto a moved parent.
+    pub parent_tip: String,
+    pub created_unix: i64,
+}
+
+/// The worktree most recently removed by gww, kept for `gww undo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemovedWorktree {
+    /// Branch the worktree had checked out; empty for detached worktrees.
+    pub branch: String,
+    pub path: PathBuf,
+    /// Commit checked out at removal, used when the branch is gone or detached.
+    pub head: String,
+    pub removed_unix: i64,
+}
+
+/// Returns the directory holding gww state for the current repository.
+pub fn state_dir() -> Result<PathBuf> {
+    let output = git_output(["rev-parse", "--git-common-dir"])?;
+    let common_dir = PathBuf::from(output.trim());
+    let common_dir = if common_dir.is_absolute() {
+        common_dir
+    } else {
+        working_dir()?.join(common_dir)
+    };
+    Ok(common_dir.join("gww"))
+}
+
+/// Reads tab-separated records from a state file, returning none when absent.
+pub fn read_records(name: &str) -> Result<Vec<Vec<String>>> {
+    let path = state_dir()?.join(name);
+    match fs::read_to_string(&path) {
+        Ok(contents) => Ok(parse_records(&contents)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Replaces a state file with the given tab-separated records.
+pub fn write_records(name: &str, records: &[Vec<String>]) -> Result<()> {
+    let dir = state_dir()?;
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(name);
+    fs::write(&path, format_records(records))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Splits state file contents into records, skipping blank lines.
+fn parse_records(contents: &str) -> Vec<Vec<String>> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.split('\t').map(|field| field.to_string()).collect())
+        .collect()
+}
+
+/// Joins records into tab-separated lines.
+fn format_records(records: &[Vec<String>]) -> String {
+    records
+        .iter()
+        .map(|record| format!("{}\n", record.join("\t")))
+        .collect()
+}
+
+/// Looks up the recorded base for a branch.
+pub fn load_branch_base(branch: &str) -> Result<Option<BranchBase>> {
+    Ok(read_records(BRANCH_BASES_FILE)?
+        .iter()
+        .filter_map(|record| branch_base_from_record(record))
+        .find(|entry| entry.branch == branch))
+}
+
+/// Records the base a branch was created from, replacing any earlier entry.
+pub fn save_branch_base(entry: &BranchBase) -> Result<()> {
+    let mut records = records_without(BRANCH_BASES_FILE, &entry.branch)?;
+    records.push(vec![
+        entry.branch.clone(),
+        entry.base.clone(),
+        entry.created_unix.to_string(),
+    ]);
+    write_records(BRANCH_BASES_FILE, &records)
+}
+
+/// Looks up the custom directory recorded for a branch's worktree.
+pub fn load_worktree_dir(branch: &str) -> Result<Option<PathBuf>> {
+    Ok(read_records(WORKTREE_DIRS_FILE)?
+        .iter()
+        .find(|record| record.first().map(String::as_str) == Some(branch))
+        .and_then(|record| record.get(1))
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from))
+}
+
+/// Records the custom directory of a branch's worktree, replacing any earlier entry.
+pub fn save_worktree_dir(branch: &str, path: &Path) -> Result<()> {
+    let mut records = records_without(WORKTREE_DIRS_FILE, branch)?;
+    records.push(vec![branch.to_string(), path.display().to_string()]);
+    write_records(WORKTREE_DIRS_FILE, &records)
+}
+
+/// Drops the recorded directory for a branch, if any.
+pub fn forget_worktree_dir(branch: &str) -> Result<()> {
+    let records = read_records(WORKTREE_DIRS_FILE)?;
+    if !records
+        .iter()
+        .any(|record| record.first().map(String::as_str) == Some(branch))
+    {
+        return Ok(());
+    }
+    write_records(
+        WORKTREE_DIRS_FILE,
+        &records_without(WORKTREE_DIRS_FILE, branch)?,
+    )
+}
+
+/// Appends a visit to the history, keeping only the most recent entries.
+pub fn record_visit(visit: &Visit) -> Result<()> {
+    let mut records = read_records(HISTORY_FILE)?;
+    records.push(vec![
+        visit.branch.clone(),
+        visit.path.display().to_string(),
+        visit.visited_unix.to_string(),
+    ]);
+    let excess = records.len().saturating_sub(HISTORY_LIMIT);
+    write_records(HISTORY_FILE, &records[excess..])
+}
+
+/// Reads the visit history, oldest first.
+pub fn load_visits() -> Result<Vec<Visit>> {
+    Ok(read_records(HISTORY_FILE)?
+        .iter()
+        .filter_map(|record| visit_from_record(record))
+        .collect())
+}
+
+/// Returns the latest visit time for each branch in the history.
+pub fn access_times(visits: &[Visit]) -> HashMap<String, i64> {
+    let mut times = HashMap::new();
+    for visit in visits.iter().filter(|visit| !visit.branch.is_empty()) {
+        let latest = times
+            .entry(visit.branch.clone())
+            .or_insert(visit.visited_unix);
+        *latest = (*latest).max(visit.visited_unix);
+    }
+    times
+}
+
+/// Converts a history record into a visit, skipping entries without a path or time.
+fn visit_from_record(record: &[String]) -> Option<Visit> {
+    let path = record.get(1).filter(|value| !value.is_empty())?;
+    let visited_unix = record.get(2)?.parse::<i64>().ok()?;
+    Some(Visit {
+        branch: record.first().cloned().unwrap_or_default(),
+        path: PathBuf::from(path),
+        visited_unix,
+    })
+}
+
+/// Records the parent of a stacked branch, replacing any earlier entry.
+pub fn save_branch_parent(entry: &BranchParent) -> Result<()> {
+    let mut records = records_without(BRANCH_PARENTS_FILE, &entry.branch)?;
+    records.push(vec![
+        entry.branch.clone(),
+        entry.parent.clone(),
+        entry.parent_tip.clone(),
+        entry.created_unix.to_string(),
+    ]);
+    write_records(BRANCH_PARENTS_FILE, &records)
+}
+
+/// Records a removed worktree, replacing the previous one.
+pub fn save_last_removed(removed: &RemovedWorktree) -> Result<()> {
+    write_records(
+        LAST_REMOVED_FILE,
+        &[vec![
+            removed.branch.clone(),
+            removed.path.display().to_string(),
+            removed.head.clone(),
+            removed.removed_unix.to_string(),
+        ]],
+    )
+}
+
+/// Reads the most recently removed worktree, if one is recorded.
+pub fn load_last_removed() -> Result<Option<RemovedWorktree>> {
+    Ok(read_records(LAST_REMOVED_FILE)?
+        .last()
+        .and_then(|record| removed_from_record(record)))
+}
+
+/// Clears the removed worktree record once it has been restored.
+pub fn forget_last_removed() -> Result<()> {
+    write_records(LAST_REMOVED_FILE, &[])
+}
+
+/// Converts a state record into a removed worktree, requiring a path and a commit.
+fn removed_from_record(record: &[String]) -> Option<RemovedWorktree> {
+    let path = record.get(1).filter(|value| !value.is_empty())?;
+    let head = record.get(2).filter(|value| !value.is_empty())?;
+    Some(RemovedWorktree {
+        branch: record.first().cloned().unwrap_or_default(),
+        path: PathBuf::from(path),
+        head: head.clone(),
+        removed_unix: record
+            .get(3)
+            .and_then(|value| value.parse::<i64>().ok())
+            .unwrap_or(0),
+    })
+}
+
+/// Reads a state file, leaving out the record keyed by `key`.
+fn records_without(name: &str, key: &str) -> Result<Vec<Vec<String>>> {
+    Ok(read_records(name)?
+        .into_iter()
+        .filter(|record| record.first().map(String::as_str) != Some(key))
+        .collect())
+}
+
+/// Converts a state record into a branch base, tolerating a missing timestamp.
+fn branch_base_from_record(record: &[String]) -> Option<BranchBase> {
+    let branch = record.first().filter(|value| !value.is_empty())?;
+    let base = record.get(1).filter(|value| !value.is_empty())?;
+    let created_unix = record
+        .get(2)
+        .and_then(|value| value.parse::<i64>().ok())
+        .unwrap_or(0);
+    Some(BranchBase {
+        branch: branch.clone(),
+        base: base.clone(),
+        created_unix,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Round-trips records through the tab-separated format.
+    #[test]
+    fn records_round_trip_and_skip_blank_lines() {
+        let records = vec![
+            vec!["feature".to_string(), "develop".to_string()],
+            vec!["fix".to_string(), "main".to_string(), "42".to_string()],
+        ];
+
+        let contents = format_records(&records);
+
+        assert_eq!(parse_records(&format!("{contents}\n\n")), records);
+    }
+
+    /// Keeps the newest visit per branch and ignores detached entries.
+    #[test]
+    fn access_times_uses_latest_visit_per_branch() {
+        let visit = |branch: &str, visited_unix| Visit {
+            branch: branch.to_string(),
+            path: PathBuf::from("/wt"),
+            visited_unix,
+        };
+        let visits = vec![visit("a", 5), visit("b", 7), visit("a", 3), visit("", 9)];
+
+        let times = access_times(&visits);
+
+        assert_eq!(times.get("a"), Some(&5));
+        assert_eq!(times.get("b"), Some(&7));
+        assert_eq!(times.len(), 2);
+    }
+
+    /// Accepts records without a creation time and rejects empty bases.
+    #[test]
+    fn branch_base_from_record_tolerates_missing_fields() {
+        let partial = vec!["feature".to_string(), "develop".to_string()];
+        let empty_base = vec!["feature".to_string(), String::new()];
+
+        assert_eq!(
+            branch_base_from_record(&partial),
+            Some(BranchBase {
+                branch: "feature".to_string(),
+                base: "develop".to_string(),
+                created_unix: 0,
+            })
+        );
+        assert_eq!(branch_base_from_record(&empty_base), None);
+    }
+
+    /// Keeps detached removals and rejects records without a commit.
+    #[test]
+    fn removed_from_record_needs_path_and_head() {
+        let detached = vec![
+            String::new(),
+            "/wt/repo/scratch".to_string(),
+            "abc123".to_string(),
+            "42".to_string(),
+        ];
+        let no_head = vec!["feature".to_string(), "/wt/repo/feature".to_string()];
+
+        assert_eq!(
+            removed_from_record(&detached),
+            Some(RemovedWorktree {
+                branch: String::new(),
+                path: PathBuf::from("/wt/repo/scratch"),
+                head: "abc123".to_string(),
+                removed_unix: 42,
+            })
+        );
+        assert_eq!(removed_from_record(&no_head), None);
+    }
+}